@@ -0,0 +1,93 @@
+//! Generate SDRAM descriptors for programming into a board EEPROM
+//!
+//! This runs on the host. For example
+//!
+//! ```text
+//! cargo run --example sdram-descriptor --features std,stm32h743v \
+//!     --target x86_64-unknown-linux-gnu -- w9825g6kh-6 descriptor.bin
+//! ```
+//!
+//! The firmware reads the descriptor back with
+//! `SdramDescriptor::from_bytes`, and passes it to `Sdram::new_dynamic`.
+
+use std::{env, fs, process};
+
+use stm32h7_fmc::{
+    is42s16400j_7, is42s32800g_6, mt48lc4m32b2_6, mt48lc4m32b2_7,
+    mt48lc4m32b2_75, sdram_chip, SdramDescriptor,
+};
+
+// Chips that are not defined in this crate can be described from their
+// datasheet
+sdram_chip! {
+    name: W9825g6kh,
+    description: "W9825G6KH",
+    rows: 8192,
+    columns: 512,
+    banks: 4,
+    width: 16,
+    startup_delay_ns: 200_000, // 200 µs
+    refresh_ms: 64,
+    grades: {
+        //                CL  max SD clock  tRAS  tRC tRCD  tRP tXSR (ns)
+        w9825g6kh_6: "6", 3, 100_000_000, [42, 60, 15, 15, 72],
+    }
+}
+
+fn main() {
+    let chips = [
+        (
+            "is42s16400j-7",
+            SdramDescriptor::from_chip::<is42s16400j_7::Is42s16400j>(),
+        ),
+        (
+            "is42s32800g-6",
+            SdramDescriptor::from_chip::<is42s32800g_6::Is42s32800g>(),
+        ),
+        (
+            "mt48lc4m32b2-6",
+            SdramDescriptor::from_chip::<mt48lc4m32b2_6::Mt48lc4m32b2>(),
+        ),
+        (
+            "mt48lc4m32b2-7",
+            SdramDescriptor::from_chip::<mt48lc4m32b2_7::Mt48lc4m32b2>(),
+        ),
+        (
+            "mt48lc4m32b2-75",
+            SdramDescriptor::from_chip::<mt48lc4m32b2_75::Mt48lc4m32b2>(),
+        ),
+        (
+            "w9825g6kh-6",
+            SdramDescriptor::from_chip::<w9825g6kh_6::W9825g6kh>(),
+        ),
+    ];
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <chip> <output file>", args[0]);
+        eprintln!("Chips:");
+        for (name, _) in chips.iter() {
+            eprintln!("    {}", name);
+        }
+        process::exit(1);
+    }
+
+    let descriptor = match chips.iter().find(|(name, _)| *name == args[1]) {
+        Some((_, descriptor)) => descriptor,
+        None => {
+            eprintln!("Unknown chip {}", args[1]);
+            process::exit(1);
+        }
+    };
+    let bytes = match descriptor.to_bytes() {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Invalid SDRAM descriptor: {:?}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(&args[2], &bytes[..]) {
+        eprintln!("Failed to write {}: {}", args[2], e);
+        process::exit(1);
+    }
+}
@@ -1,9 +1,6 @@
 #![no_main]
 #![no_std]
 
-use core::mem;
-use core::slice;
-
 extern crate cortex_m_rt as rt;
 use core::sync::atomic::{AtomicU32, Ordering};
 use rt::{entry, exception};
@@ -20,7 +17,7 @@ use stm32h7_fmc::is42s32800g_6;
 use stm32h7xx_hal::gpio::Speed;
 use stm32h7xx_hal::hal::digital::v2::OutputPin;
 use stm32h7xx_hal::rcc::CoreClocks;
-use stm32h7xx_hal::{prelude::*, stm32};
+use stm32h7xx_hal::{prelude::*, stm32, stm32::interrupt};
 
 use cortex_m_log::log::{trick_init, Logger};
 use cortex_m_log::{
@@ -225,23 +222,29 @@ fn main() -> ! {
         is42s32800g_6::Is42s32800g {},
     );
 
-    // Initialise controller and SDRAM
-    let ram = unsafe {
-        let ram_ptr: *mut u32 = sdram.init(&mut delay, ccdr.clocks);
-        let ram_size_bytes = 32 * 1024 * 1024;
+    // Count refresh errors in the FMC interrupt
+    sdram.listen_refresh_error();
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(stm32::Interrupt::FMC);
+    }
 
-        // MPU config for SDRAM write-through
-        mpu_sdram_init(&mut cp.MPU, &mut cp.SCB, ram_ptr, ram_size_bytes);
+    // Initialise controller and SDRAM
+    let mut sdram = sdram.init(&mut delay, ccdr.clocks);
+    let region = sdram.region();
+
+    // MPU config for SDRAM write-through
+    mpu_sdram_init(
+        &mut cp.MPU,
+        &mut cp.SCB,
+        region.as_ptr(),
+        region.size_bytes(),
+    );
 
-        info!("");
-        info!("");
-        info!("Initialised MPU...");
+    info!("");
+    info!("");
+    info!("Initialised MPU...");
 
-        slice::from_raw_parts_mut(
-            ram_ptr,
-            ram_size_bytes / mem::size_of::<u32>(),
-        )
-    };
+    let ram = region.into_u32_slice();
 
     info!("Initialised SDRAM...");
 
@@ -298,6 +301,13 @@ fn SysTick() {
     TIME.fetch_add(1, Ordering::Relaxed);
 }
 
+#[interrupt]
+fn FMC() {
+    if stm32h7_fmc::on_refresh_error_interrupt() {
+        warn!("SDRAM refresh error");
+    }
+}
+
 #[exception]
 fn HardFault(ef: &cortex_m_rt::ExceptionFrame) -> ! {
     panic!("HardFault at {:#?}", ef);
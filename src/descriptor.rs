@@ -0,0 +1,629 @@
+//! Binary encoding of SDRAM descriptors
+//!
+//! An [`SdramDescriptor`] can be stored in a board EEPROM or OTP
+//! memory, and read back by the firmware to configure the SDRAM. The
+//! encoding is versioned and protected by a CRC, in a similar way to
+//! the Serial Presence Detect (SPD) data on a DIMM.
+//!
+//! Version 1 is 62 bytes long. Multi-byte fields are little-endian.
+//!
+//! | Offset | Size | Field                                          |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | Magic `"SDRM"`                                 |
+//! | 4      | 1    | Version (1)                                    |
+//! | 5      | 1    | Length of the descriptor in bytes (62)         |
+//! | 6      | 1    | Column bits                                    |
+//! | 7      | 1    | Row bits                                       |
+//! | 8      | 1    | Memory data width                              |
+//! | 9      | 1    | Internal banks                                 |
+//! | 10     | 1    | CAS latency                                    |
+//! | 11     | 1    | Read pipe delay cycles                         |
+//! | 12     | 1    | SD clock divide (0 = Auto, 2 or 3)             |
+//! | 13     | 1    | Flags, see below                               |
+//! | 14     | 4    | Startup delay (ns)                             |
+//! | 18     | 4    | Maximum SD clock (Hz)                          |
+//! | 22     | 4    | Refresh period (ns)                            |
+//! | 26     | 30   | tMRD, tXSR, tRAS, tRC, tRP, tRCD               |
+//! | 56     | 2    | Mode register                                  |
+//! | 58     | 2    | Extended mode register                         |
+//! | 60     | 2    | CRC-16/XMODEM of bytes 0 ~ 59                  |
+//!
+//! Each timing parameter is a 4 byte time in nanoseconds followed by
+//! a 1 byte number of cycles, see [`SdramTime`]. The flags are
+//!
+//! * Bit 0: Write protection
+//! * Bit 1: Read burst
+//! * Bit 2: The extended mode register is present
+//!
+//! All other bits are reserved, and must be zero.
+//!
+//! [`SdramDescriptor`]: ../struct.SdramDescriptor.html
+//! [`SdramTime`]: ../struct.SdramTime.html
+
+use core::fmt;
+
+use crate::sdram::{
+    BurstLength, BurstType, DriveStrength, ExtendedModeRegister,
+    FmcSdramConfiguration, FmcSdramTiming, ModeRegister, OperatingMode,
+    PartialArraySelfRefresh, SdClockDivide, SdramDescriptor, SdramError,
+    SdramTime, TemperatureCompensatedSelfRefresh, WriteBurstMode,
+};
+
+/// Magic number at the start of an encoded descriptor
+const MAGIC: [u8; 4] = *b"SDRM";
+
+const FLAG_WRITE_PROTECTION: u8 = 1 << 0;
+const FLAG_READ_BURST: u8 = 1 << 1;
+const FLAG_EXTENDED_MODE_REGISTER: u8 = 1 << 2;
+
+/// Errors that can occur when decoding an SDRAM descriptor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DescriptorError {
+    /// The buffer is shorter than the descriptor. Contains the length
+    /// of the buffer
+    TooShort(usize),
+    /// The buffer does not start with the magic number
+    BadMagic,
+    /// The version of the encoding is not supported
+    UnsupportedVersion(u8),
+    /// The length in the header is wrong for this version
+    InvalidLength(u8),
+    /// The CRC does not match the contents of the descriptor
+    CrcMismatch {
+        /// CRC stored in the descriptor
+        expected: u16,
+        /// CRC calculated from the contents of the descriptor
+        actual: u16,
+    },
+    /// A field has a reserved value. Contains the name of the field
+    InvalidField(&'static str),
+    /// The descriptor was decoded, but is not valid
+    Invalid(SdramError),
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptorError::TooShort(len) => {
+                write!(f, "descriptor truncated to {} bytes", len)
+            }
+            DescriptorError::BadMagic => f.write_str("not an SDRAM descriptor"),
+            DescriptorError::UnsupportedVersion(version) => {
+                write!(f, "unsupported descriptor version {}", version)
+            }
+            DescriptorError::InvalidLength(len) => {
+                write!(f, "invalid descriptor length {}", len)
+            }
+            DescriptorError::CrcMismatch { expected, actual } => write!(
+                f,
+                "CRC mismatch: expected {:#06x}, got {:#06x}",
+                expected, actual
+            ),
+            DescriptorError::InvalidField(field) => {
+                write!(f, "invalid {}", field)
+            }
+            DescriptorError::Invalid(e) => {
+                write!(f, "invalid SDRAM descriptor: {:?}", e)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DescriptorError {}
+
+/// CRC-16/XMODEM, as used by JEDEC SPD
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Writes fields to a buffer in order
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+    fn time(&mut self, time: SdramTime) {
+        self.u32(time.ns);
+        self.u8(time.cycles);
+    }
+}
+
+/// Reads fields from a buffer in order. The buffer must be long
+/// enough for all the fields that are read
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> u8 {
+        self.pos += 1;
+        self.buf[self.pos - 1]
+    }
+    fn u16(&mut self) -> u16 {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(&self.buf[self.pos..self.pos + 2]);
+        self.pos += 2;
+        u16::from_le_bytes(bytes)
+    }
+    fn u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.buf[self.pos..self.pos + 4]);
+        self.pos += 4;
+        u32::from_le_bytes(bytes)
+    }
+    fn time(&mut self) -> SdramTime {
+        let ns = self.u32();
+        let cycles = self.u8();
+        SdramTime { ns, cycles }
+    }
+}
+
+/// Decode the value of a mode register
+fn decode_mode_register(bits: u16) -> Result<ModeRegister, DescriptorError> {
+    let burst_length = match bits & 0b111 {
+        0b000 => BurstLength::One,
+        0b001 => BurstLength::Two,
+        0b010 => BurstLength::Four,
+        0b011 => BurstLength::Eight,
+        0b111 => BurstLength::FullPage,
+        _ => return Err(DescriptorError::InvalidField("burst length")),
+    };
+    let burst_type = match (bits >> 3) & 1 {
+        0 => BurstType::Sequential,
+        _ => BurstType::Interleaved,
+    };
+    let operating_mode = match (bits >> 7) & 0b11 {
+        0b00 => OperatingMode::Standard,
+        _ => return Err(DescriptorError::InvalidField("operating mode")),
+    };
+    let write_burst_mode = match (bits >> 9) & 1 {
+        0 => WriteBurstMode::Programmed,
+        _ => WriteBurstMode::Single,
+    };
+    if bits >> 10 != 0 {
+        return Err(DescriptorError::InvalidField("mode register"));
+    }
+
+    Ok(ModeRegister {
+        burst_length,
+        burst_type,
+        cas_latency: ((bits >> 4) & 0b111) as u8,
+        operating_mode,
+        write_burst_mode,
+    })
+}
+
+/// Decode the value of an extended mode register
+fn decode_extended_mode_register(
+    bits: u16,
+) -> Result<ExtendedModeRegister, DescriptorError> {
+    let partial_array_self_refresh = match bits & 0b111 {
+        0b000 => PartialArraySelfRefresh::Full,
+        0b001 => PartialArraySelfRefresh::Half,
+        0b010 => PartialArraySelfRefresh::Quarter,
+        0b101 => PartialArraySelfRefresh::Eighth,
+        0b110 => PartialArraySelfRefresh::Sixteenth,
+        _ => {
+            return Err(DescriptorError::InvalidField(
+                "partial array self-refresh",
+            ))
+        }
+    };
+    let temperature_compensated_self_refresh = match (bits >> 3) & 0b11 {
+        0b00 => TemperatureCompensatedSelfRefresh::Max70C,
+        0b01 => TemperatureCompensatedSelfRefresh::Max45C,
+        0b10 => TemperatureCompensatedSelfRefresh::Max15C,
+        _ => TemperatureCompensatedSelfRefresh::Max85C,
+    };
+    let drive_strength = match (bits >> 5) & 0b11 {
+        0b00 => DriveStrength::Full,
+        0b01 => DriveStrength::Half,
+        0b10 => DriveStrength::Quarter,
+        _ => DriveStrength::Eighth,
+    };
+    if bits >> 7 != 0 {
+        return Err(DescriptorError::InvalidField("extended mode register"));
+    }
+
+    Ok(ExtendedModeRegister {
+        partial_array_self_refresh,
+        temperature_compensated_self_refresh,
+        drive_strength,
+    })
+}
+
+impl SdramDescriptor {
+    /// Version of the binary encoding written by
+    /// [`to_bytes`](#method.to_bytes)
+    pub const ENCODING_VERSION: u8 = 1;
+    /// Length of the binary encoding in bytes
+    pub const ENCODED_LEN: usize = 62;
+
+    /// Encode the descriptor. See the [`descriptor`](descriptor/index.html)
+    /// module for the format.
+    ///
+    /// The descriptor is checked with [`validate`](#method.validate)
+    /// first, so that only valid descriptors are encoded.
+    pub fn to_bytes(
+        &self,
+    ) -> Result<[u8; SdramDescriptor::ENCODED_LEN], SdramError> {
+        self.validate()?;
+
+        let mut buf = [0; SdramDescriptor::ENCODED_LEN];
+        let mut w = Writer {
+            buf: &mut buf,
+            pos: 0,
+        };
+
+        w.bytes(&MAGIC);
+        w.u8(SdramDescriptor::ENCODING_VERSION);
+        w.u8(SdramDescriptor::ENCODED_LEN as u8);
+
+        let config = &self.config;
+        w.u8(config.column_bits);
+        w.u8(config.row_bits);
+        w.u8(config.memory_data_width);
+        w.u8(config.internal_banks);
+        w.u8(config.cas_latency);
+        w.u8(config.read_pipe_delay_cycles);
+        w.u8(match config.sd_clock_divide {
+            SdClockDivide::Auto => 0,
+            SdClockDivide::Div2 => 2,
+            SdClockDivide::Div3 => 3,
+        });
+        let mut flags = 0;
+        if config.write_protection {
+            flags |= FLAG_WRITE_PROTECTION;
+        }
+        if config.read_burst {
+            flags |= FLAG_READ_BURST;
+        }
+        if self.extended_mode_register.is_some() {
+            flags |= FLAG_EXTENDED_MODE_REGISTER;
+        }
+        w.u8(flags);
+
+        let timing = &self.timing;
+        w.u32(timing.startup_delay_ns);
+        w.u32(timing.max_sd_clock_hz);
+        w.u32(timing.refresh_period_ns);
+        w.time(timing.mode_register_to_active);
+        w.time(timing.exit_self_refresh);
+        w.time(timing.active_to_precharge);
+        w.time(timing.row_cycle);
+        w.time(timing.row_precharge);
+        w.time(timing.row_to_column);
+
+        w.u16(self.mode_register.bits());
+        w.u16(self.extended_mode_register.map_or(0, |emr| emr.bits()));
+
+        let crc = crc16(&w.buf[..w.pos]);
+        w.u16(crc);
+
+        Ok(buf)
+    }
+
+    /// Decode a descriptor that was encoded with
+    /// [`to_bytes`](#method.to_bytes). The buffer may be longer than
+    /// the descriptor.
+    ///
+    /// The decoded descriptor is checked with
+    /// [`validate`](#method.validate).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DescriptorError> {
+        // Header
+        if bytes.len() < 6 {
+            return Err(DescriptorError::TooShort(bytes.len()));
+        }
+        if bytes[..4] != MAGIC {
+            return Err(DescriptorError::BadMagic);
+        }
+        if bytes[4] != SdramDescriptor::ENCODING_VERSION {
+            return Err(DescriptorError::UnsupportedVersion(bytes[4]));
+        }
+        if bytes[5] as usize != SdramDescriptor::ENCODED_LEN {
+            return Err(DescriptorError::InvalidLength(bytes[5]));
+        }
+        if bytes.len() < SdramDescriptor::ENCODED_LEN {
+            return Err(DescriptorError::TooShort(bytes.len()));
+        }
+
+        // CRC
+        let (contents, crc) =
+            bytes[..SdramDescriptor::ENCODED_LEN].split_at(60);
+        let expected = u16::from_le_bytes([crc[0], crc[1]]);
+        let actual = crc16(contents);
+        if expected != actual {
+            return Err(DescriptorError::CrcMismatch { expected, actual });
+        }
+
+        let mut r = Reader {
+            buf: contents,
+            pos: 6,
+        };
+        let column_bits = r.u8();
+        let row_bits = r.u8();
+        let memory_data_width = r.u8();
+        let internal_banks = r.u8();
+        let cas_latency = r.u8();
+        let read_pipe_delay_cycles = r.u8();
+        let sd_clock_divide = match r.u8() {
+            0 => SdClockDivide::Auto,
+            2 => SdClockDivide::Div2,
+            3 => SdClockDivide::Div3,
+            _ => return Err(DescriptorError::InvalidField("sd clock divide")),
+        };
+        let flags = r.u8();
+        if flags
+            & !(FLAG_WRITE_PROTECTION
+                | FLAG_READ_BURST
+                | FLAG_EXTENDED_MODE_REGISTER)
+            != 0
+        {
+            return Err(DescriptorError::InvalidField("flags"));
+        }
+        let config = FmcSdramConfiguration {
+            column_bits,
+            row_bits,
+            memory_data_width,
+            internal_banks,
+            cas_latency,
+            write_protection: flags & FLAG_WRITE_PROTECTION != 0,
+            sd_clock_divide,
+            read_burst: flags & FLAG_READ_BURST != 0,
+            read_pipe_delay_cycles,
+        };
+
+        let timing = FmcSdramTiming {
+            startup_delay_ns: r.u32(),
+            max_sd_clock_hz: r.u32(),
+            refresh_period_ns: r.u32(),
+            mode_register_to_active: r.time(),
+            exit_self_refresh: r.time(),
+            active_to_precharge: r.time(),
+            row_cycle: r.time(),
+            row_precharge: r.time(),
+            row_to_column: r.time(),
+        };
+
+        let mode_register = decode_mode_register(r.u16())?;
+        let extended_mode_register = r.u16();
+        let extended_mode_register = if flags & FLAG_EXTENDED_MODE_REGISTER != 0
+        {
+            Some(decode_extended_mode_register(extended_mode_register)?)
+        } else {
+            None
+        };
+
+        let descriptor = SdramDescriptor {
+            mode_register,
+            extended_mode_register,
+            config,
+            timing,
+        };
+        descriptor.validate().map_err(DescriptorError::Invalid)?;
+
+        Ok(descriptor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        is42s16400j_7::Is42s16400j, is42s32800g_6::Is42s32800g,
+        mt48lc4m32b2_6::Mt48lc4m32b2, SdramChip,
+    };
+
+    /// Encoding of an IS42S32800G-6 descriptor
+    fn encoded() -> [u8; SdramDescriptor::ENCODED_LEN] {
+        SdramDescriptor::from_chip::<Is42s32800g>()
+            .to_bytes()
+            .unwrap()
+    }
+
+    /// Recalculate the CRC after changing the contents
+    fn update_crc(bytes: &mut [u8; SdramDescriptor::ENCODED_LEN]) {
+        let crc = crc16(&bytes[..60]);
+        bytes[60..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    #[test]
+    fn crc() {
+        // CRC-16/XMODEM check value
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn round_trip() {
+        fn round_trip<IC: SdramChip>() {
+            let descriptor = SdramDescriptor::from_chip::<IC>();
+            let bytes = descriptor.to_bytes().unwrap();
+            assert_eq!(SdramDescriptor::from_bytes(&bytes), Ok(descriptor));
+        }
+        round_trip::<Is42s32800g>();
+        round_trip::<Is42s16400j>();
+        round_trip::<Mt48lc4m32b2>();
+
+        // Extended mode register
+        let mut descriptor = SdramDescriptor::from_chip::<Is42s32800g>();
+        descriptor.extended_mode_register = Some(
+            ExtendedModeRegister::new()
+                .partial_array_self_refresh(PartialArraySelfRefresh::Quarter)
+                .temperature_compensated_self_refresh(
+                    TemperatureCompensatedSelfRefresh::Max45C,
+                )
+                .drive_strength(DriveStrength::Half),
+        );
+        let bytes = descriptor.to_bytes().unwrap();
+        assert_eq!(SdramDescriptor::from_bytes(&bytes), Ok(descriptor));
+
+        // Trailing bytes are ignored
+        let mut longer = [0xFF; 64];
+        longer[..SdramDescriptor::ENCODED_LEN].copy_from_slice(&encoded());
+        assert_eq!(
+            SdramDescriptor::from_bytes(&longer),
+            Ok(SdramDescriptor::from_chip::<Is42s32800g>())
+        );
+    }
+
+    #[test]
+    fn header() {
+        let bytes = encoded();
+        assert_eq!(&bytes[..6], b"SDRM\x01\x3E");
+        assert_eq!(bytes[10], 3); // CAS latency
+                                  // CL3, BL1, single write burst
+        assert_eq!(&bytes[56..58], &0x0230u16.to_le_bytes());
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let mut bytes = encoded();
+        let expected = u16::from_le_bytes([bytes[60], bytes[61]]);
+        bytes[18] ^= 1;
+        let actual = crc16(&bytes[..60]);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::CrcMismatch { expected, actual })
+        );
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut bytes = encoded();
+        bytes[0] = b's';
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::BadMagic)
+        );
+
+        let mut bytes = encoded();
+        bytes[4] = 2;
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::UnsupportedVersion(2))
+        );
+
+        let mut bytes = encoded();
+        bytes[5] = 64;
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidLength(64))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = encoded();
+        assert_eq!(
+            SdramDescriptor::from_bytes(&[]),
+            Err(DescriptorError::TooShort(0))
+        );
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes[..5]),
+            Err(DescriptorError::TooShort(5))
+        );
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes[..61]),
+            Err(DescriptorError::TooShort(61))
+        );
+    }
+
+    #[test]
+    fn reserved_bits() {
+        // Flags
+        let mut bytes = encoded();
+        bytes[13] |= 1 << 3;
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidField("flags"))
+        );
+
+        // SD clock divide
+        let mut bytes = encoded();
+        bytes[12] = 1;
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidField("sd clock divide"))
+        );
+
+        // Mode register
+        let mut bytes = encoded();
+        bytes[57] |= 1 << 2;
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidField("mode register"))
+        );
+
+        // Operating mode
+        let mut bytes = encoded();
+        bytes[56] |= 1 << 7;
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidField("operating mode"))
+        );
+
+        // Extended mode register, only checked when it is present
+        let mut bytes = encoded();
+        bytes[59] = 0xFF;
+        update_crc(&mut bytes);
+        assert!(SdramDescriptor::from_bytes(&bytes).is_ok());
+        bytes[13] |= FLAG_EXTENDED_MODE_REGISTER;
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::InvalidField("extended mode register"))
+        );
+    }
+
+    #[test]
+    fn invalid_descriptor() {
+        // Decoded, but not valid
+        let mut bytes = encoded();
+        bytes[7] = 14; // Row bits
+        update_crc(&mut bytes);
+        assert_eq!(
+            SdramDescriptor::from_bytes(&bytes),
+            Err(DescriptorError::Invalid(SdramError::InvalidRowBits(14)))
+        );
+
+        // Invalid descriptors are not encoded
+        let mut descriptor = SdramDescriptor::from_chip::<Is42s32800g>();
+        descriptor.mode_register.cas_latency = 4;
+        descriptor.config.cas_latency = 4;
+        assert_eq!(
+            descriptor.to_bytes(),
+            Err(SdramError::InvalidCasLatency(4))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! HAL for Flexible memory controller (FMC)
 
+use crate::registers::{bcr1, FmcRegister, FmcRegisters};
 use crate::stm32::FMC;
 
 use stm32h7xx_hal::rcc::{rec, rec::ResetEnable, CoreClocks};
@@ -57,14 +58,39 @@ impl FmcBank {
     }
 }
 
+/// Mapping of the FMC banks into the memory map, set by the BMAP bits
+/// of FMC_BCR1
+///
+/// The default SDRAM region at 0xC000_0000 is Execute-Never in the
+/// default Cortex-M7 memory map. Remapping the SDRAM to 0x6000_0000 or
+/// 0x7000_0000 allows code to be run from the SDRAM, with the default
+/// cacheable memory attributes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BankMapping {
+    /// SDRAM bank 1 at 0xC000_0000, SDRAM bank 2 at 0xD000_0000
+    Default,
+    /// NOR/PSRAM and SDRAM banks swapped. SDRAM bank 1 at 0x6000_0000,
+    /// SDRAM bank 2 at 0x7000_0000
+    Swapped,
+    /// SDRAM bank 2 remapped to 0x7000_0000, and still accessible at
+    /// 0xD000_0000. SDRAM bank 1 at 0xC000_0000
+    SdramBank2Remapped,
+}
+
 /// FMC controller
 #[allow(missing_debug_implementations)]
 pub struct Fmc {
     /// Flexible memory controller (FMC)
     pub(crate) fmc: FMC,
 
+    /// FMC peripheral record
+    rec: rec::Fmc,
+
     /// FMC clock selection
     clk_sel: rec::FmcClkSel,
+
+    /// FMC bank mapping
+    bank_mapping: BankMapping,
 }
 
 /// Set of pins for an SDRAM
@@ -72,6 +98,7 @@ pub trait PinsSdram<FMC> {
     const EXTERNAL_BANK: u8;
     const NUMBER_INTERNAL_BANKS: u8;
     const ADDRESS_LINES: u8;
+    const DATA_WIDTH: u8;
 }
 
 /// Set of pins for SDRAM on Bank 1 of FMC controller
@@ -80,25 +107,71 @@ pub struct PinsSdramBank1<T>(pub T);
 /// Set of pins for SDRAM on Bank 2 of FMC controller
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct PinsSdramBank2<T>(pub T);
+/// Set of pins for SDRAM with 11 address lines (A0 ~ A10) and 4 internal
+/// banks (BA0 and BA1) on Bank 1 of FMC controller
+///
+/// This set has the same number of pins as a set with 12 address lines
+/// and BA0 only, so it can't be a `PinsSdramBank1`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PinsSdramBank1A10Ba1<T>(pub T);
+/// Set of pins for SDRAM with 11 address lines (A0 ~ A10) and 4 internal
+/// banks (BA0 and BA1) on Bank 2 of FMC controller
+///
+/// This set has the same number of pins as a set with 12 address lines
+/// and BA0 only, so it can't be a `PinsSdramBank2`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PinsSdramBank2A10Ba1<T>(pub T);
+
+macro_rules! impl_8bit_sdram {
+    ($($pins:tt: [$eBankN:expr, $ckeN:tt, $neN:tt,
+        $nAddress:expr $(, $pa:ident, $a:tt)*;
+        $nInternalB:expr $(, $pba1:ident, $ba1:tt)*]),+) => {
+        $(
+            #[rustfmt::skip]
+            /// 8-bit SDRAM
+            impl<FMC, PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
+            $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
+            PNBL0, PSDCKEn, PSDCLK, PSDNCAS, PSDNEn, PSDNRAS, PSDNWE>
+                PinsSdram<FMC>
+                for $pins<(PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
+                     $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
+                     PNBL0, PSDCKEn, PSDCLK, PSDNCAS, PSDNEn, PSDNRAS, PSDNWE)>
+            where PA0: A0<FMC>, PA1: A1<FMC>, PA2: A2<FMC>, PA3: A3<FMC>, PA4:
+            A4<FMC>, PA5: A5<FMC>, PA6: A6<FMC>, PA7: A7<FMC>, PA8: A8<FMC>, PA9:
+            A9<FMC>, PA10: A10<FMC>, $($pa: $a<FMC>,)* PBA0: BA0<FMC>,
+            $($pba1:$ba1<FMC>,)*
+            PD0: D0<FMC>, PD1: D1<FMC>, PD2: D2<FMC>, PD3: D3<FMC>, PD4:
+            D4<FMC>, PD5: D5<FMC>, PD6: D6<FMC>, PD7: D7<FMC>, PNBL0: NBL0<FMC>,
+            PSDCKEn: $ckeN<FMC>, PSDCLK: SDCLK<FMC>, PSDNCAS: SDNCAS<FMC>,
+            PSDNEn: $neN<FMC>, PSDNRAS: SDNRAS<FMC>, PSDNWE: SDNWE<FMC> {
+                const ADDRESS_LINES: u8 = $nAddress;
+                const EXTERNAL_BANK: u8 = $eBankN;
+                const NUMBER_INTERNAL_BANKS: u8 = $nInternalB;
+                const DATA_WIDTH: u8 = 8;
+            }
+        )+
+    }
+}
 
 macro_rules! impl_16bit_sdram {
     ($($pins:tt: [$eBankN:expr, $ckeN:tt, $neN:tt,
+        $nAddress:expr $(, $pa:ident, $a:tt)*;
         $nInternalB:expr $(, $pba1:ident, $ba1:tt)*]),+) => {
         $(
             #[rustfmt::skip]
             /// 16-bit SDRAM
             impl<FMC, PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
-            PA11, PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8,
+            $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8,
             PD9, PD10, PD11, PD12, PD13, PD14, PD15, PNBL0, PNBL1, PSDCKEn,
             PSDCLK, PSDNCAS, PSDNEn, PSDNRAS, PSDNWE>
                 PinsSdram<FMC>
                 for $pins<(PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
-                     PA11, PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
+                     $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
                      PD8, PD9, PD10, PD11, PD12, PD13, PD14, PD15, PNBL0, PNBL1,
                      PSDCKEn, PSDCLK, PSDNCAS, PSDNEn, PSDNRAS, PSDNWE)>
             where PA0: A0<FMC>, PA1: A1<FMC>, PA2: A2<FMC>, PA3: A3<FMC>, PA4:
             A4<FMC>, PA5: A5<FMC>, PA6: A6<FMC>, PA7: A7<FMC>, PA8: A8<FMC>, PA9:
-            A9<FMC>, PA10: A10<FMC>, PA11: A11<FMC>, PBA0: BA0<FMC>,
+            A9<FMC>, PA10: A10<FMC>, $($pa: $a<FMC>,)* PBA0: BA0<FMC>,
             $($pba1:$ba1<FMC>,)*
             PD0: D0<FMC>, PD1: D1<FMC>, PD2: D2<FMC>, PD3: D3<FMC>, PD4:
             D4<FMC>, PD5: D5<FMC>, PD6: D6<FMC>, PD7: D7<FMC>, PD8: D8<FMC>, PD9:
@@ -106,9 +179,10 @@ macro_rules! impl_16bit_sdram {
             D13<FMC>, PD14: D14<FMC>, PD15: D15<FMC>, PNBL0: NBL0<FMC>, PNBL1:
             NBL1<FMC>, PSDCKEn: $ckeN<FMC>, PSDCLK: SDCLK<FMC>, PSDNCAS:
             SDNCAS<FMC>, PSDNEn: $neN<FMC>, PSDNRAS: SDNRAS<FMC>, PSDNWE: SDNWE<FMC> {
-                const ADDRESS_LINES: u8 = 12;
+                const ADDRESS_LINES: u8 = $nAddress;
                 const EXTERNAL_BANK: u8 = $eBankN;
                 const NUMBER_INTERNAL_BANKS: u8 = $nInternalB;
+                const DATA_WIDTH: u8 = 16;
             }
         )+
     }
@@ -116,26 +190,27 @@ macro_rules! impl_16bit_sdram {
 
 macro_rules! impl_32bit_sdram {
     ($($pins:tt: [$eBankN:expr, $ckeN:tt, $neN:tt,
+        $nAddress:expr $(, $pa:ident, $a:tt)*;
         $nInternalB:expr $(, $pba1:ident, $ba1:tt)*]),+) => {
         $(
             #[rustfmt::skip]
             /// 32-bit SDRAM
             impl<FMC, PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
-            PA11, PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8,
+            $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7, PD8,
             PD9, PD10, PD11, PD12, PD13, PD14, PD15, PD16, PD17, PD18, PD19,
             PD20, PD21, PD22, PD23, PD24, PD25, PD26, PD27, PD28, PD29, PD30,
             PD31, PNBL0, PNBL1, PNBL2, PNBL3, PSDCKEn, PSDCLK, PSDNCAS,
             PSDNEn, PSDNRAS, PSDNWE>
                 PinsSdram<FMC>
                 for $pins<(PA0, PA1, PA2, PA3, PA4, PA5, PA6, PA7, PA8, PA9, PA10,
-                     PA11, PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
+                     $($pa,)* PBA0, $($pba1,)* PD0, PD1, PD2, PD3, PD4, PD5, PD6, PD7,
                      PD8, PD9, PD10, PD11, PD12, PD13, PD14, PD15, PD16, PD17,
                      PD18, PD19, PD20, PD21, PD22, PD23, PD24, PD25, PD26, PD27,
                      PD28, PD29, PD30, PD31, PNBL0, PNBL1, PNBL2, PNBL3, PSDCKEn,
                      PSDCLK, PSDNCAS, PSDNEn, PSDNRAS, PSDNWE)>
             where PA0: A0<FMC>, PA1: A1<FMC>, PA2: A2<FMC>, PA3: A3<FMC>, PA4:
             A4<FMC>, PA5: A5<FMC>, PA6: A6<FMC>, PA7: A7<FMC>, PA8: A8<FMC>, PA9:
-            A9<FMC>, PA10: A10<FMC>, PA11: A11<FMC>, PBA0: BA0<FMC>,
+            A9<FMC>, PA10: A10<FMC>, $($pa: $a<FMC>,)* PBA0: BA0<FMC>,
             $($pba1:$ba1<FMC>,)*
             PD0: D0<FMC>, PD1: D1<FMC>, PD2: D2<FMC>, PD3: D3<FMC>, PD4:
             D4<FMC>, PD5: D5<FMC>, PD6: D6<FMC>, PD7: D7<FMC>, PD8: D8<FMC>, PD9:
@@ -148,30 +223,80 @@ macro_rules! impl_32bit_sdram {
             NBL1<FMC>, PNBL2: NBL2<FMC>, PNBL3: NBL3<FMC>, PSDCKEn: $ckeN<FMC>,
                   PSDCLK: SDCLK<FMC>, PSDNCAS: SDNCAS<FMC>, PSDNEn: $neN<FMC>, PSDNRAS:
             SDNRAS<FMC>, PSDNWE: SDNWE<FMC> {
-                const ADDRESS_LINES: u8 = 12;
+                const ADDRESS_LINES: u8 = $nAddress;
                 const EXTERNAL_BANK: u8 = $eBankN;
                 const NUMBER_INTERNAL_BANKS: u8 = $nInternalB;
+                const DATA_WIDTH: u8 = 32;
             }
         )+
     }
 }
 
+impl_8bit_sdram! {
+    // 8-bit SDRAM with 11 address lines, BA0 only
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 11; 2],
+    // 8-bit SDRAM with 11 address lines, BA0 and BA1. This has the same
+    // number of pins as a set with 12 address lines and BA0 only, so it
+    // has its own wrapper types
+    PinsSdramBank1A10Ba1: [1, SDCKE0, SDNE0, 11; 4, PBA1, BA1],
+    PinsSdramBank2A10Ba1: [2, SDCKE1, SDNE1, 11; 4, PBA1, BA1],
+    // 8-bit SDRAM with 12 address lines, BA0 only
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 2],
+    // 8-bit SDRAM with 12 address lines, BA0 and BA1
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 4, PBA1, BA1],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 4, PBA1, BA1]
+}
+
 impl_16bit_sdram! {
+    // 16-bit SDRAM with 11 address lines, BA0 only
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 11; 2],
+    // 16-bit SDRAM with 11 address lines, BA0 and BA1. This has the same
+    // number of pins as a set with 12 address lines and BA0 only, so it
+    // has its own wrapper types
+    PinsSdramBank1A10Ba1: [1, SDCKE0, SDNE0, 11; 4, PBA1, BA1],
+    PinsSdramBank2A10Ba1: [2, SDCKE1, SDNE1, 11; 4, PBA1, BA1],
     // 16-bit SDRAM with 12 address lines, BA0 only
-    PinsSdramBank1: [1, SDCKE0, SDNE0, 2],
-    PinsSdramBank2: [2, SDCKE1, SDNE1, 2],
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 2],
     // 16-bit SDRAM with 12 address lines, BA0 and BA1
-    PinsSdramBank1: [1, SDCKE0, SDNE0, 4, PBA1, BA1],
-    PinsSdramBank2: [2, SDCKE1, SDNE1, 4, PBA1, BA1]
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 4, PBA1, BA1],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 4, PBA1, BA1],
+    // 16-bit SDRAM with 13 address lines, BA0 and BA1. There is no
+    // set with BA0 only, as it would have the same number of pins as a
+    // set with 12 address lines, BA0 and BA1. SDRAMs with 13 row bits
+    // have 4 internal banks
+    PinsSdramBank1: [1, SDCKE0, SDNE0,
+                     13, PA11, A11, PA12, A12; 4, PBA1, BA1],
+    PinsSdramBank2: [2, SDCKE1, SDNE1,
+                     13, PA11, A11, PA12, A12; 4, PBA1, BA1]
 }
 
 impl_32bit_sdram! {
+    // 32-bit SDRAM with 11 address lines, BA0 only
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 11; 2],
+    // 32-bit SDRAM with 11 address lines, BA0 and BA1. This has the same
+    // number of pins as a set with 12 address lines and BA0 only, so it
+    // has its own wrapper types
+    PinsSdramBank1A10Ba1: [1, SDCKE0, SDNE0, 11; 4, PBA1, BA1],
+    PinsSdramBank2A10Ba1: [2, SDCKE1, SDNE1, 11; 4, PBA1, BA1],
     // 32-bit SDRAM with 12 address lines, BA0 only
-    PinsSdramBank1: [1, SDCKE0, SDNE0, 2],
-    PinsSdramBank2: [2, SDCKE1, SDNE1, 2],
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 2],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 2],
     // 32-bit SDRAM with 12 address lines, BA0 and BA1
-    PinsSdramBank1: [1, SDCKE0, SDNE0, 4, PBA1, BA1],
-    PinsSdramBank2: [2, SDCKE1, SDNE1, 4, PBA1, BA1]
+    PinsSdramBank1: [1, SDCKE0, SDNE0, 12, PA11, A11; 4, PBA1, BA1],
+    PinsSdramBank2: [2, SDCKE1, SDNE1, 12, PA11, A11; 4, PBA1, BA1],
+    // 32-bit SDRAM with 13 address lines, BA0 and BA1. There is no
+    // set with BA0 only, as it would have the same number of pins as a
+    // set with 12 address lines, BA0 and BA1. SDRAMs with 13 row bits
+    // have 4 internal banks
+    PinsSdramBank1: [1, SDCKE0, SDNE0,
+                     13, PA11, A11, PA12, A12; 4, PBA1, BA1],
+    PinsSdramBank2: [2, SDCKE1, SDNE1,
+                     13, PA11, A11, PA12, A12; 4, PBA1, BA1]
 }
 macro_rules! pins {
     (FMC: $($pin:ident: [$($inst:ty),*])+) => {
@@ -438,7 +563,12 @@ impl Fmc {
         let rec_fmc = rec_fmc.enable().reset();
         let clk_sel = rec_fmc.get_kernel_clk_mux();
 
-        Fmc { fmc, clk_sel }
+        Fmc {
+            fmc,
+            rec: rec_fmc,
+            clk_sel,
+            bank_mapping: BankMapping::Default,
+        }
     }
 
     /// Current kernel clock (`fmc_ker_ck`)
@@ -451,10 +581,179 @@ impl Fmc {
         }
     }
 
-    /// Enable FMC controller
-    pub(crate) fn enable(&mut self) {
-        // The FMCEN bit of the FMC_BCR2..4 registers is don’t
-        // care. It is only enabled through the FMC_BCR1 register.
-        self.fmc.bcr1.modify(|_, w| w.fmcen().set_bit());
+    /// Current FMC bank mapping
+    pub(crate) fn bank_mapping(&self) -> BankMapping {
+        self.bank_mapping
+    }
+
+    /// Set the FMC bank mapping
+    pub(crate) fn set_bank_mapping(&mut self, bank_mapping: BankMapping) {
+        let bmap = match bank_mapping {
+            BankMapping::Default => 0b00,
+            BankMapping::Swapped => 0b01,
+            BankMapping::SdramBank2Remapped => 0b10,
+        };
+        unsafe {
+            self.fmc
+                .modify(FmcRegister::Bcr1, |r| bcr1::BMAP.set(r, bmap));
+        }
+
+        self.bank_mapping = bank_mapping;
+    }
+
+    /// Disable the FMC controller, then reset it and stop its kernel
+    /// clock. Returns the FMC peripheral and its peripheral record
+    pub(crate) fn free(self) -> (FMC, rec::Fmc) {
+        unsafe {
+            self.fmc
+                .modify(FmcRegister::Bcr1, |r| bcr1::FMCEN.set(r, 0));
+        }
+
+        (self.fmc, self.rec.reset().disable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdram::{check_pins, check_pins_dynamic};
+    use crate::{FmcSdramConfiguration, SdramChip, SdramError};
+    use mt48lc16m8a2_75::Mt48lc16m8a2;
+
+    /// A pin that can be used for any FMC signal
+    struct P;
+
+    pins! {
+        FMC:
+        A0: [P] A1: [P] A2: [P] A3: [P] A4: [P] A5: [P] A6: [P] A7: [P]
+        A8: [P] A9: [P] A10: [P] A11: [P] BA0: [P] BA1: [P]
+        D0: [P] D1: [P] D2: [P] D3: [P] D4: [P] D5: [P] D6: [P] D7: [P]
+        D8: [P] D9: [P] D10: [P] D11: [P] D12: [P] D13: [P] D14: [P]
+        D15: [P] NBL0: [P] NBL1: [P]
+        SDCKE0: [P] SDCKE1: [P] SDCLK: [P] SDNCAS: [P] SDNE0: [P]
+        SDNE1: [P] SDNRAS: [P] SDNWE: [P]
+    }
+
+    /// 16-bit SDRAM pins with 13 address and bank address lines
+    #[rustfmt::skip]
+    type Pins16Bit = (
+        // A0 ~ A10, then A11 or BA0, then BA0 or BA1
+        P, P, P, P, P, P, P, P, P, P, P, P, P,
+        // D0 ~ D15
+        P, P, P, P, P, P, P, P, P, P, P, P, P, P, P, P,
+        // NBL0, NBL1, SDCKEn, SDCLK, SDNCAS, SDNEn, SDNRAS, SDNWE
+        P, P, P, P, P, P, P, P,
+    );
+
+    /// 8-bit SDRAM pins with 12, 13 and 14 address and bank address
+    /// lines
+    #[rustfmt::skip]
+    type Pins8Bit12 = (
+        // A0 ~ A10, BA0
+        P, P, P, P, P, P, P, P, P, P, P, P,
+        // D0 ~ D7
+        P, P, P, P, P, P, P, P,
+        // NBL0, SDCKEn, SDCLK, SDNCAS, SDNEn, SDNRAS, SDNWE
+        P, P, P, P, P, P, P,
+    );
+    #[rustfmt::skip]
+    type Pins8Bit13 = (
+        // A0 ~ A10, then A11 or BA0, then BA0 or BA1
+        P, P, P, P, P, P, P, P, P, P, P, P, P,
+        // D0 ~ D7
+        P, P, P, P, P, P, P, P,
+        // NBL0, SDCKEn, SDCLK, SDNCAS, SDNEn, SDNRAS, SDNWE
+        P, P, P, P, P, P, P,
+    );
+    #[rustfmt::skip]
+    type Pins8Bit14 = (
+        // A0 ~ A11, BA0, BA1
+        P, P, P, P, P, P, P, P, P, P, P, P, P, P,
+        // D0 ~ D7
+        P, P, P, P, P, P, P, P,
+        // NBL0, SDCKEn, SDCLK, SDNCAS, SDNEn, SDNRAS, SDNWE
+        P, P, P, P, P, P, P,
+    );
+
+    // Micron MT48LC16M8A2, an 8-bit SDRAM
+    sdram_chip! {
+        name: Mt48lc16m8a2,
+        description: "MT48LC16M8A2",
+        rows: 4096,
+        columns: 1024,
+        banks: 4,
+        width: 8,
+        startup_delay_ns: 100_000, // 100 µs
+        refresh_ms: 64,
+        grades: {
+            // CL, max SD clock, then tRAS, tRC, tRCD, tRP, tXSR (ns)
+            mt48lc16m8a2_75: "75", 3, 100_000_000, [44, 66, 20, 20, 75],
+        }
+    }
+
+    /// Check an 8-bit set of pins against 8-bit and 16-bit SDRAMs with
+    /// as many rows and internal banks as the pins can access
+    fn check_8bit<PINS: PinsSdram<FMC>>(address_lines: u8, internal_banks: u8) {
+        assert_eq!(PINS::DATA_WIDTH, 8);
+        assert_eq!(PINS::ADDRESS_LINES, address_lines);
+        assert_eq!(PINS::NUMBER_INTERNAL_BANKS, internal_banks);
+
+        let config = FmcSdramConfiguration {
+            column_bits: 10,
+            row_bits: address_lines,
+            memory_data_width: 8,
+            internal_banks,
+            ..Mt48lc16m8a2::CONFIG
+        };
+        assert_eq!(check_pins_dynamic::<PINS>(&config), Ok(()));
+
+        // 16-bit SDRAM on 8-bit pins
+        let config = FmcSdramConfiguration {
+            memory_data_width: 16,
+            ..config
+        };
+        assert_eq!(
+            check_pins_dynamic::<PINS>(&config),
+            Err(SdramError::PinsDataWidthMismatch { pins: 8, sdram: 16 })
+        );
+    }
+
+    #[test]
+    fn pins_8bit() {
+        // A0 ~ A10, BA0
+        check_8bit::<PinsSdramBank1<Pins8Bit12>>(11, 2);
+        check_8bit::<PinsSdramBank2<Pins8Bit12>>(11, 2);
+        // A0 ~ A10, BA0, BA1
+        check_8bit::<PinsSdramBank1A10Ba1<Pins8Bit13>>(11, 4);
+        check_8bit::<PinsSdramBank2A10Ba1<Pins8Bit13>>(11, 4);
+        // A0 ~ A11, BA0
+        check_8bit::<PinsSdramBank1<Pins8Bit13>>(12, 2);
+        check_8bit::<PinsSdramBank2<Pins8Bit13>>(12, 2);
+        // A0 ~ A11, BA0, BA1
+        check_8bit::<PinsSdramBank1<Pins8Bit14>>(12, 4);
+        check_8bit::<PinsSdramBank2<Pins8Bit14>>(12, 4);
+
+        // An 8-bit chip definition
+        check_pins::<Mt48lc16m8a2, PinsSdramBank1<Pins8Bit14>>();
+        check_pins::<Mt48lc16m8a2, PinsSdramBank2<Pins8Bit14>>();
+    }
+
+    fn lines<PINS: PinsSdram<FMC>>() -> (u8, u8, u8, u8) {
+        (
+            PINS::EXTERNAL_BANK,
+            PINS::ADDRESS_LINES,
+            PINS::NUMBER_INTERNAL_BANKS,
+            PINS::DATA_WIDTH,
+        )
+    }
+
+    #[test]
+    fn same_number_of_pins() {
+        // A0 ~ A11, BA0
+        assert_eq!(lines::<PinsSdramBank1<Pins16Bit>>(), (1, 12, 2, 16));
+        assert_eq!(lines::<PinsSdramBank2<Pins16Bit>>(), (2, 12, 2, 16));
+        // A0 ~ A10, BA0, BA1
+        assert_eq!(lines::<PinsSdramBank1A10Ba1<Pins16Bit>>(), (1, 11, 4, 16));
+        assert_eq!(lines::<PinsSdramBank2A10Ba1<Pins16Bit>>(), (2, 11, 4, 16));
     }
 }
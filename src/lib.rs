@@ -1,6 +1,16 @@
 //! ## This crate has been replaced by [FMC support within
 //! stm32h7xx-hal](https://docs.rs/stm32h7xx-hal/latest/stm32h7xx_hal/fmc/index.html).
 //!
+//! ## Testing
+//!
+//! The tests run on the host, against an in-memory model of the FMC
+//! registers. `.cargo/config` builds for `thumbv7em-none-eabihf` by
+//! default, so give the host target explicitly:
+//!
+//! ```text
+//! cargo test --lib --features stm32h743v --target x86_64-unknown-linux-gnu
+//! ```
+//!
 //! ## License
 //!
 //! Licensed under either of
@@ -20,7 +30,7 @@
 //! above, without any additional terms or conditions.
 //!
 //! [`stm32h7xx-hal`]: https://crates.io/crates/stm32h7xx-hal
-#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 // rustc lints.
 #![warn(
     bare_trait_objects,
@@ -46,12 +56,36 @@ use stm32h7xx_hal::hal;
 use stm32h7xx_hal::stm32;
 
 mod fmc;
-pub use fmc::{PinsSdramBank1, PinsSdramBank2};
+pub use fmc::{
+    BankMapping, PinsSdramBank1, PinsSdramBank1A10Ba1, PinsSdramBank2,
+    PinsSdramBank2A10Ba1,
+};
+
+pub mod descriptor;
+pub use descriptor::DescriptorError;
+
+mod region;
+pub use region::SdramRegion;
+
+pub mod registers;
+#[cfg(any(test, feature = "std"))]
+pub use registers::FakeFmc;
+pub use registers::{FmcRegister, FmcRegisters};
 
 mod sdram;
-pub use sdram::Sdram;
+pub use sdram::state;
+pub use sdram::{
+    init_sequence, init_sequence_dual, on_refresh_error_interrupt, validate,
+    BurstLength, BurstType, DriveStrength, ExtendedModeRegister,
+    FmcSdramConfiguration, FmcSdramTiming, ModeRegister, OperatingMode,
+    PartialArraySelfRefresh, SdClockDivide, Sdram, SdramChip, SdramDescriptor,
+    SdramDual, SdramError, SdramTime, TemperatureCompensatedSelfRefresh,
+    WriteBurstMode,
+};
 
 mod is42s32800g;
 pub use is42s32800g::*;
 mod is42s16400j;
 pub use is42s16400j::*;
+mod mt48lc4m32b2;
+pub use mt48lc4m32b2::*;
@@ -17,3 +17,280 @@ mod log {
 macro_rules! fmc_trace {
     ($($arg:expr),*) => (fmc_log!(trace, $($arg),*));
 }
+
+/// Like the `?` operator, but can be used in a `const fn`
+macro_rules! const_try {
+    ($e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(e) => return Err(e),
+        }
+    };
+}
+
+/// Define an SDRAM chip from its datasheet
+///
+/// The organisation of the chip is given as the number of rows,
+/// columns and internal banks, and the data width in bits. The
+/// refresh period is given in milliseconds for all rows. For each
+/// speed grade, the CAS latency and the maximum SD clock at that CAS
+/// latency are given, followed by the timings in nanoseconds: tRAS,
+/// tRC, tRCD, tRP and tXSR.
+///
+/// Optionally, the following can be given after `refresh_ms`:
+///
+/// * `mode_register_to_active`: tMRD, as an `SdramTime`. Defaults to 2
+///   cycles
+/// * `read_burst`: Defaults to `true`
+/// * `read_pipe_delay_cycles`: Defaults to 0
+///
+/// A module is generated for each speed grade, containing a type
+/// `name` that implements [`SdramChip`](trait.SdramChip.html). The
+/// mode register uses single location bursts, and the CAS latency of
+/// the speed grade.
+///
+/// See the chip modules in this crate for examples.
+#[macro_export]
+macro_rules! sdram_chip {
+    (@or $value:expr, $default:expr) => {
+        $value
+    };
+    (@or , $default:expr) => {
+        $default
+    };
+    (
+        @chip
+        name: $name:ident,
+        description: $description:expr,
+        rows: $rows:expr,
+        columns: $columns:expr,
+        banks: $banks:expr,
+        width: $width:expr,
+        startup_delay_ns: $startup_delay_ns:expr,
+        refresh_ms: $refresh_ms:expr,
+        mode_register_to_active: $t_mrd:expr,
+        read_burst: $read_burst:expr,
+        read_pipe_delay_cycles: $read_pipe_delay_cycles:expr,
+        grades: {
+            $($module:ident: $grade:expr, $cas_latency:expr,
+              $max_sd_clock_hz:expr, [$t_ras:expr, $t_rc:expr,
+              $t_rcd:expr, $t_rp:expr, $t_xsr:expr]),+ $(,)?
+        }
+    ) => {
+        $(
+            #[doc = concat!("Speed Grade ", $grade)]
+            pub mod $module {
+                use $crate::{
+                    BurstLength, BurstType, FmcSdramConfiguration,
+                    FmcSdramTiming, ModeRegister, OperatingMode,
+                    SdClockDivide, SdramChip, SdramTime, WriteBurstMode,
+                };
+
+                #[doc = concat!($description, " with Speed Grade ", $grade)]
+                #[derive(Clone, Copy, Debug, PartialEq)]
+                pub struct $name {}
+
+                impl SdramChip for $name {
+                    const MODE_REGISTER: ModeRegister = ModeRegister::new()
+                        .burst_length(BurstLength::One)
+                        .burst_type(BurstType::Sequential)
+                        .cas_latency($cas_latency)
+                        .operating_mode(OperatingMode::Standard)
+                        .write_burst_mode(WriteBurstMode::Single);
+
+                    const TIMING: FmcSdramTiming = FmcSdramTiming {
+                        startup_delay_ns: $startup_delay_ns,
+                        max_sd_clock_hz: $max_sd_clock_hz,
+                        refresh_period_ns: $refresh_ms * 1_000_000 / $rows,
+
+                        mode_register_to_active: $t_mrd,
+                        exit_self_refresh: SdramTime::ns($t_xsr),
+                        active_to_precharge: SdramTime::ns($t_ras),
+                        row_cycle: SdramTime::ns($t_rc),
+                        row_precharge: SdramTime::ns($t_rp),
+                        row_to_column: SdramTime::ns($t_rcd),
+                    };
+
+                    const CONFIG: FmcSdramConfiguration =
+                        FmcSdramConfiguration {
+                            column_bits: u32::trailing_zeros($columns) as u8,
+                            row_bits: u32::trailing_zeros($rows) as u8,
+                            memory_data_width: $width,
+                            internal_banks: $banks,
+                            cas_latency: $cas_latency,
+                            write_protection: false,
+                            sd_clock_divide: SdClockDivide::Auto,
+                            read_burst: $read_burst,
+                            read_pipe_delay_cycles: $read_pipe_delay_cycles,
+                        };
+                }
+
+                // Check the organisation
+                const _: () = assert!(
+                    u32::is_power_of_two($rows)
+                        && u32::is_power_of_two($columns),
+                    "Number of rows and columns must be a power of 2"
+                );
+                // Check the datasheet timings are consistent: tRC is at
+                // least tRAS + tRP
+                const _: () = assert!(
+                    $t_rc >= $t_ras + $t_rp,
+                    "tRC must be at least tRAS + tRP"
+                );
+                // Check the chip definition at compile time
+                const _: () = <$name as SdramChip>::VALID;
+            }
+        )+
+    };
+    (
+        name: $name:ident,
+        description: $description:expr,
+        rows: $rows:expr,
+        columns: $columns:expr,
+        banks: $banks:expr,
+        width: $width:expr,
+        startup_delay_ns: $startup_delay_ns:expr,
+        refresh_ms: $refresh_ms:expr,
+        $(mode_register_to_active: $t_mrd:expr,)?
+        $(read_burst: $read_burst:expr,)?
+        $(read_pipe_delay_cycles: $read_pipe_delay_cycles:expr,)?
+        grades: { $($grades:tt)+ } $(,)?
+    ) => {
+        $crate::sdram_chip! {
+            @chip
+            name: $name,
+            description: $description,
+            rows: $rows,
+            columns: $columns,
+            banks: $banks,
+            width: $width,
+            startup_delay_ns: $startup_delay_ns,
+            refresh_ms: $refresh_ms,
+            mode_register_to_active: $crate::sdram_chip!(
+                @or $($t_mrd)?, $crate::SdramTime::cycles(2)
+            ),
+            read_burst: $crate::sdram_chip!(@or $($read_burst)?, true),
+            read_pipe_delay_cycles: $crate::sdram_chip!(
+                @or $($read_pipe_delay_cycles)?, 0
+            ),
+            grades: { $($grades)+ }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        FmcSdramConfiguration, FmcSdramTiming, SdClockDivide, SdramChip,
+        SdramTime,
+    };
+
+    sdram_chip! {
+        name: Overridden,
+        description: "Overridden",
+        rows: 8192,
+        columns: 512,
+        banks: 4,
+        width: 16,
+        startup_delay_ns: 200_000,
+        refresh_ms: 64,
+        mode_register_to_active: SdramTime::ns(15).min_cycles(2),
+        read_burst: false,
+        read_pipe_delay_cycles: 1,
+        grades: {
+            overridden_6: "6", 3, 100_000_000, [42, 60, 15, 15, 72],
+            overridden_7: "7", 2, 90_000_000, [42, 63, 15, 15, 70],
+        }
+    }
+
+    sdram_chip! {
+        name: Defaults,
+        description: "Defaults",
+        rows: 4096,
+        columns: 256,
+        banks: 2,
+        width: 8,
+        startup_delay_ns: 100_000,
+        refresh_ms: 32,
+        grades: {
+            defaults_7: "7", 2, 100_000_000, [42, 63, 15, 15, 70]
+        }
+    }
+
+    #[test]
+    fn overridden() {
+        use overridden_6::Overridden as Overridden6;
+        use overridden_7::Overridden as Overridden7;
+
+        let config = FmcSdramConfiguration {
+            column_bits: 9,
+            row_bits: 13,
+            memory_data_width: 16,
+            internal_banks: 4,
+            cas_latency: 3,
+            write_protection: false,
+            sd_clock_divide: SdClockDivide::Auto,
+            read_burst: false,
+            read_pipe_delay_cycles: 1,
+        };
+        let timing = FmcSdramTiming {
+            startup_delay_ns: 200_000,
+            max_sd_clock_hz: 100_000_000,
+            refresh_period_ns: 7_812, // 64ms / 8192 rows
+            mode_register_to_active: SdramTime::ns(15).min_cycles(2),
+            exit_self_refresh: SdramTime::ns(72),
+            active_to_precharge: SdramTime::ns(42),
+            row_cycle: SdramTime::ns(60),
+            row_precharge: SdramTime::ns(15),
+            row_to_column: SdramTime::ns(15),
+        };
+        assert_eq!(Overridden6::CONFIG, config);
+        assert_eq!(Overridden6::TIMING, timing);
+        assert_eq!(Overridden6::MODE_REGISTER.cas_latency, 3);
+
+        // CAS latency, maximum SD clock and timings of the speed grade
+        assert_eq!(
+            Overridden7::CONFIG,
+            FmcSdramConfiguration {
+                cas_latency: 2,
+                ..config
+            }
+        );
+        assert_eq!(
+            Overridden7::TIMING,
+            FmcSdramTiming {
+                max_sd_clock_hz: 90_000_000,
+                exit_self_refresh: SdramTime::ns(70),
+                row_cycle: SdramTime::ns(63),
+                ..timing
+            }
+        );
+        assert_eq!(Overridden7::MODE_REGISTER.cas_latency, 2);
+    }
+
+    #[test]
+    fn defaults() {
+        use defaults_7::Defaults;
+
+        assert_eq!(
+            Defaults::CONFIG,
+            FmcSdramConfiguration {
+                column_bits: 8,
+                row_bits: 12,
+                memory_data_width: 8,
+                internal_banks: 2,
+                cas_latency: 2,
+                write_protection: false,
+                sd_clock_divide: SdClockDivide::Auto,
+                read_burst: true,
+                read_pipe_delay_cycles: 0,
+            }
+        );
+        assert_eq!(
+            Defaults::TIMING.mode_register_to_active,
+            SdramTime::cycles(2)
+        );
+        assert_eq!(Defaults::TIMING.refresh_period_ns, 7_812); // 32ms / 4096
+        assert_eq!(Defaults::EXTENDED_MODE_REGISTER, None);
+    }
+}
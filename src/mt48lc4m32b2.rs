@@ -1,60 +1,72 @@
-/// Micron MT48LC4M32B2 SDRAM
-#[allow(unused)]
-
-/// UNTESTED!
-
-/// Speed Grade 6
-pub mod mt48lc4m32b2_6 {
-    use crate::sdram::{FmcSdramConfiguration, FmcSdramTiming, SdramChip};
+// Micron MT48LC4M32B2 SDRAM
+//
+// Limited to 100MHz
+sdram_chip! {
+    name: Mt48lc4m32b2,
+    description: "MT48LC4M32B2",
+    rows: 4096,
+    columns: 256,
+    banks: 4,
+    width: 32,
+    startup_delay_ns: 100_000, // 100 µs
+    refresh_ms: 64,
+    grades: {
+        //                    CL  max SD clock  tRAS  tRC tRCD  tRP tXSR (ns)
+        mt48lc4m32b2_6: "6",   3, 100_000_000, [42, 60, 18, 18, 70],
+        mt48lc4m32b2_7: "7",   3, 100_000_000, [42, 70, 20, 20, 70],
+        mt48lc4m32b2_75: "75", 3, 100_000_000, [44, 66, 20, 20, 75],
+    }
+}
 
-    const BURST_LENGTH_1: u16 = 0x0000;
-    const BURST_LENGTH_2: u16 = 0x0001;
-    const BURST_LENGTH_4: u16 = 0x0002;
-    const BURST_LENGTH_8: u16 = 0x0004;
-    const BURST_TYPE_SEQUENTIAL: u16 = 0x0000;
-    const BURST_TYPE_INTERLEAVED: u16 = 0x0008;
-    const CAS_LATENCY_2: u16 = 0x0020;
-    const CAS_LATENCY_3: u16 = 0x0030;
-    const OPERATING_MODE_STANDARD: u16 = 0x0000;
-    const WRITEBURST_MODE_PROGRAMMED: u16 = 0x0000;
-    const WRITEBURST_MODE_SINGLE: u16 = 0x0200;
+#[cfg(test)]
+mod tests {
+    use super::{mt48lc4m32b2_6, mt48lc4m32b2_7, mt48lc4m32b2_75};
+    use crate::{SdramChip, SdramTime};
 
-    /// MT48LC4M32B2 with Speed Grade 6
-    #[derive(Clone, Copy, Debug, PartialEq)]
-    pub struct Mt48lc4m32b2 {}
+    /// tRAS, tRC, tRCD, tRP and tXSR
+    fn timings<IC: SdramChip>() -> [SdramTime; 5] {
+        let t = IC::TIMING;
+        [
+            t.active_to_precharge,
+            t.row_cycle,
+            t.row_to_column,
+            t.row_precharge,
+            t.exit_self_refresh,
+        ]
+    }
 
-    impl SdramChip for Mt48lc4m32b2 {
-        /// Value of the mode register
-        const MODE_REGISTER: u16 = BURST_LENGTH_1
-            | BURST_TYPE_SEQUENTIAL
-            | CAS_LATENCY_3
-            | OPERATING_MODE_STANDARD
-            | WRITEBURST_MODE_SINGLE;
+    #[test]
+    fn datasheet() {
+        // Minimum timings in the AC Characteristics table of the
+        // MT48LC4M32B2 datasheet
+        let ns = |t: [u32; 5]| t.map(SdramTime::ns);
+        assert_eq!(
+            timings::<mt48lc4m32b2_6::Mt48lc4m32b2>(),
+            ns([42, 60, 18, 18, 70])
+        );
+        assert_eq!(
+            timings::<mt48lc4m32b2_7::Mt48lc4m32b2>(),
+            ns([42, 70, 20, 20, 70])
+        );
+        assert_eq!(
+            timings::<mt48lc4m32b2_75::Mt48lc4m32b2>(),
+            ns([44, 66, 20, 20, 75])
+        );
 
-        /// Timing Parameters
-        const TIMING: FmcSdramTiming = FmcSdramTiming {
-            startup_delay_ns: 100_000,    // 100 µs
-            max_sd_clock_hz: 100_000_000, // 100 MHz
-            refresh_period_ns: 15_625,    // 64ms / (4096 rows) = 15625ns
-            mode_register_to_active: 2,   // tMRD = 2 cycles
-            exit_self_refresh: 7,         // tXSR = 70ns
-            active_to_precharge: 4,       // tRAS = 42ns
-            row_cycle: 7,                 // tRC = 70ns
-            row_precharge: 2,             // tRP = 18ns
-            row_to_column: 2,             // tRCD = 18ns
-        };
+        // tMRD is 2 cycles, and 4096 rows are refreshed every 64ms
+        let t = mt48lc4m32b2_6::Mt48lc4m32b2::TIMING;
+        assert_eq!(t.mode_register_to_active, SdramTime::cycles(2));
+        assert_eq!(t.refresh_period_ns, 15_625);
+    }
 
-        /// SDRAM controller configuration
-        const CONFIG: FmcSdramConfiguration = FmcSdramConfiguration {
-            column_bits: 9,
-            row_bits: 12,
-            memory_data_width: 32, // 32-bit
-            internal_banks: 4,     // 4 internal banks
-            cas_latency: 3,        // CAS latency = 2
-            write_protection: false,
-            sd_clock_divide: 2, // Divide by 2
-            read_burst: true,
-            read_pipe_delay_cycles: 0,
-        };
+    #[test]
+    fn size() {
+        // 4 banks x 4096 rows x 256 columns x 32 bits
+        assert_eq!(mt48lc4m32b2_6::Mt48lc4m32b2::CONFIG.size_bytes(), 16 << 20);
+        assert_eq!(mt48lc4m32b2_7::Mt48lc4m32b2::CONFIG.size_bytes(), 16 << 20);
+        assert_eq!(
+            mt48lc4m32b2_75::Mt48lc4m32b2::CONFIG.size_bytes(),
+            16 << 20
+        );
     }
 }
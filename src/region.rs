@@ -0,0 +1,254 @@
+//! Memory-mapped SDRAM regions
+
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::slice;
+
+/// A region of memory-mapped SDRAM
+///
+/// Returned when an SDRAM is initialised. The region borrows the SDRAM
+/// controller, so it cannot outlive the SDRAM being initialised. If
+/// the controller has a `'static` lifetime, then so does the region.
+///
+/// A region can be divided into smaller, non-overlapping regions with
+/// [`split_at`](#method.split_at), or typed buffers can be taken from
+/// it with [`carve`](#method.carve).
+#[derive(Debug)]
+pub struct SdramRegion<'a> {
+    /// Base address
+    ptr: *mut u8,
+    /// Length in bytes
+    size_bytes: usize,
+    /// Borrow of the SDRAM controller
+    _sdram: PhantomData<&'a mut ()>,
+}
+
+// The region has exclusive access to its memory, so it can be moved to
+// another context
+unsafe impl Send for SdramRegion<'_> {}
+
+impl<'a> SdramRegion<'a> {
+    /// New SDRAM region
+    ///
+    /// # Safety
+    ///
+    /// The region from `ptr` to `ptr + size_bytes` must be valid memory
+    /// for `'a`, and must not be accessed by anything else.
+    pub(crate) unsafe fn new(ptr: *mut u32, size_bytes: usize) -> Self {
+        SdramRegion {
+            ptr: ptr as *mut u8,
+            size_bytes,
+            _sdram: PhantomData,
+        }
+    }
+
+    /// Base address of the region
+    pub fn as_ptr(&self) -> *mut u32 {
+        self.ptr as *mut u32
+    }
+
+    /// Length of the region in bytes
+    pub const fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    /// Divide the region into two at `mid` bytes. The first region
+    /// contains bytes `[0, mid)` and the second contains bytes `[mid,
+    /// size_bytes)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > size_bytes`.
+    pub fn split_at(self, mid: usize) -> (Self, Self) {
+        assert!(mid <= self.size_bytes, "Split point outside SDRAM region");
+
+        unsafe {
+            (
+                SdramRegion::new(self.ptr as *mut u32, mid),
+                SdramRegion::new(
+                    self.ptr.add(mid) as *mut u32,
+                    self.size_bytes - mid,
+                ),
+            )
+        }
+    }
+
+    /// Take a buffer of `count` elements of type `T` from the start of
+    /// this region. The buffer is aligned to the larger of `align` and
+    /// the alignment of `T`. The memory is not initialised.
+    ///
+    /// Returns `None` if there is not enough space left in the
+    /// region, in which case the region is unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of 2.
+    pub fn carve<T>(
+        &mut self,
+        count: usize,
+        align: usize,
+    ) -> Option<&'a mut [MaybeUninit<T>]> {
+        assert!(align.is_power_of_two(), "Alignment must be a power of 2");
+        let align = cmp::max(align, align_of::<T>());
+
+        // Padding to align the start of the buffer
+        let start = self.ptr as usize;
+        let padding = (start.checked_add(align - 1)? & !(align - 1)) - start;
+        let length = size_of::<T>().checked_mul(count)?.checked_add(padding)?;
+        if length > self.size_bytes {
+            return None;
+        }
+
+        unsafe {
+            let buffer = self.ptr.add(padding) as *mut MaybeUninit<T>;
+            self.ptr = self.ptr.add(length);
+            self.size_bytes -= length;
+
+            Some(slice::from_raw_parts_mut(buffer, count))
+        }
+    }
+
+    /// View the region as a slice of 32-bit words. Any trailing bytes
+    /// are not included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the region is not aligned to 4 bytes.
+    pub fn into_u32_slice(self) -> &'a mut [u32] {
+        assert_eq!(self.ptr as usize % 4, 0, "SDRAM region is not aligned");
+
+        unsafe {
+            slice::from_raw_parts_mut(self.ptr as *mut u32, self.size_bytes / 4)
+        }
+    }
+
+    /// View the region as a slice of 16-bit half-words. Any trailing bytes
+    /// are not included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the region is not aligned to 2 bytes.
+    pub fn into_u16_slice(self) -> &'a mut [u16] {
+        assert_eq!(self.ptr as usize % 2, 0, "SDRAM region is not aligned");
+
+        unsafe {
+            slice::from_raw_parts_mut(self.ptr as *mut u16, self.size_bytes / 2)
+        }
+    }
+
+    /// View the region as a slice of bytes
+    pub fn into_u8_slice(self) -> &'a mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.size_bytes) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory for a region, aligned to 64 bytes
+    #[repr(C, align(64))]
+    struct Memory([u8; 256]);
+
+    fn region(memory: &mut Memory) -> SdramRegion<'_> {
+        unsafe { SdramRegion::new(memory.0.as_mut_ptr() as *mut u32, 256) }
+    }
+
+    #[test]
+    fn split_at() {
+        let mut memory = Memory([0; 256]);
+        let base = memory.0.as_ptr() as usize;
+
+        let (a, b) = region(&mut memory).split_at(100);
+        assert_eq!((a.as_ptr() as usize, a.size_bytes()), (base, 100));
+        assert_eq!((b.as_ptr() as usize, b.size_bytes()), (base + 100, 156));
+
+        // The regions do not overlap
+        a.into_u8_slice().iter_mut().for_each(|x| *x = 1);
+        b.into_u8_slice().iter_mut().for_each(|x| *x = 2);
+        assert!(memory.0[..100].iter().all(|x| *x == 1));
+        assert!(memory.0[100..].iter().all(|x| *x == 2));
+
+        // Either region can be empty
+        let (a, b) = region(&mut memory).split_at(0);
+        assert_eq!((a.size_bytes(), b.size_bytes()), (0, 256));
+        let (a, b) = region(&mut memory).split_at(256);
+        assert_eq!((a.size_bytes(), b.size_bytes()), (256, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn split_at_outside() {
+        let mut memory = Memory([0; 256]);
+        let _ = region(&mut memory).split_at(257);
+    }
+
+    #[test]
+    fn carve() {
+        let mut memory = Memory([0; 256]);
+        let base = memory.0.as_ptr() as usize;
+        let mut region = region(&mut memory);
+
+        let a = region.carve::<u8>(3, 1).unwrap();
+        assert_eq!((a.as_ptr() as usize, a.len()), (base, 3));
+        assert_eq!(region.size_bytes(), 253);
+
+        // Padded to the alignment of `T`
+        let b = region.carve::<u32>(2, 1).unwrap();
+        assert_eq!((b.as_ptr() as usize, b.len()), (base + 4, 2));
+        assert_eq!(region.size_bytes(), 244);
+
+        // Padded to `align`
+        let c = region.carve::<u16>(8, 32).unwrap();
+        assert_eq!((c.as_ptr() as usize, c.len()), (base + 32, 8));
+        assert_eq!(region.size_bytes(), 208);
+
+        // The buffers do not overlap
+        a.iter_mut().for_each(|x| *x = MaybeUninit::new(0xAA));
+        b.iter_mut()
+            .for_each(|x| *x = MaybeUninit::new(0xBBBB_BBBB));
+        c.iter_mut().for_each(|x| *x = MaybeUninit::new(0xCCCC));
+        let rest = region.into_u8_slice();
+        rest.iter_mut().for_each(|x| *x = 0xDD);
+
+        assert_eq!(&memory.0[..3], &[0xAA; 3]);
+        assert_eq!(&memory.0[4..12], &[0xBB; 8]);
+        assert_eq!(&memory.0[32..48], &[0xCC; 16]);
+        assert!(memory.0[48..].iter().all(|x| *x == 0xDD));
+    }
+
+    #[test]
+    fn carve_too_large() {
+        let mut memory = Memory([0; 256]);
+        let base = memory.0.as_ptr() as usize;
+        let mut region = region(&mut memory);
+        let _ = region.carve::<u8>(1, 1).unwrap();
+
+        // Region is unchanged when there is not enough space
+        assert!(region.carve::<u8>(256, 1).is_none());
+        assert!(region.carve::<u32>(64, 1).is_none());
+        assert!(region.carve::<u8>(193, 64).is_none()); // 63 bytes padding
+        assert!(region.carve::<u64>(usize::MAX, 1).is_none());
+        assert_eq!(
+            (region.as_ptr() as usize, region.size_bytes()),
+            (base + 1, 255)
+        );
+
+        // All of the remaining space
+        let buffer = region.carve::<u8>(255, 1).unwrap();
+        assert_eq!((buffer.as_ptr() as usize, buffer.len()), (base + 1, 255));
+        assert_eq!(region.size_bytes(), 0);
+    }
+
+    #[test]
+    fn carve_address_overflow() {
+        // The padding calculation would overflow. The region is never
+        // accessed
+        let ptr = (usize::MAX - 3) as *mut u32;
+        let mut region = unsafe { SdramRegion::new(ptr, 4) };
+
+        assert!(region.carve::<u8>(1, 8).is_none());
+        assert_eq!((region.as_ptr(), region.size_bytes()), (ptr, 4));
+    }
+}
@@ -0,0 +1,339 @@
+//! Access to the FMC registers
+//!
+//! The SDRAM driver accesses the FMC registers through the
+//! [`FmcRegisters`] trait. This is implemented for the FMC register
+//! block of the PAC, and for [`FakeFmc`], an in-memory model of the
+//! registers that can be used to run the driver on the host. `FakeFmc`
+//! is only available with the `std` feature, and in this crate's tests.
+//!
+//! [`FmcRegisters`]: trait.FmcRegisters.html
+//! [`FakeFmc`]: struct.FakeFmc.html
+
+#[cfg(any(test, feature = "std"))]
+use core::cell::Cell;
+
+use crate::stm32;
+
+/// FMC registers used by this crate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FmcRegister {
+    /// SRAM/NOR-Flash chip-select control register 1
+    Bcr1,
+    /// SDRAM control register for SDRAM bank 1
+    Sdcr1,
+    /// SDRAM control register for SDRAM bank 2
+    Sdcr2,
+    /// SDRAM timing register for SDRAM bank 1
+    Sdtr1,
+    /// SDRAM timing register for SDRAM bank 2
+    Sdtr2,
+    /// SDRAM command mode register
+    Sdcmr,
+    /// SDRAM refresh timer register
+    Sdrtr,
+    /// SDRAM status register
+    Sdsr,
+}
+
+/// A field in an FMC register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    /// Position of the least significant bit
+    pub offset: u8,
+    /// Width in bits
+    pub width: u8,
+}
+
+impl Field {
+    const fn new(offset: u8, width: u8) -> Self {
+        Field { offset, width }
+    }
+    /// Mask of the field in the register
+    pub const fn mask(self) -> u32 {
+        ((1 << self.width) - 1) << self.offset
+    }
+    /// Value of the field in `register`
+    pub const fn get(self, register: u32) -> u32 {
+        (register & self.mask()) >> self.offset
+    }
+    /// `register` with the field set to `value`
+    pub const fn set(self, register: u32, value: u32) -> u32 {
+        (register & !self.mask()) | ((value << self.offset) & self.mask())
+    }
+}
+
+/// Fields of BCR1
+pub mod bcr1 {
+    use super::Field;
+    /// FMC controller enable
+    pub const FMCEN: Field = Field::new(31, 1);
+    /// FMC bank mapping
+    pub const BMAP: Field = Field::new(24, 2);
+}
+
+/// Fields of SDCR1 and SDCR2
+pub mod sdcr {
+    use super::Field;
+    /// Number of column address bits, minus 8
+    pub const NC: Field = Field::new(0, 2);
+    /// Number of row address bits, minus 11
+    pub const NR: Field = Field::new(2, 2);
+    /// Memory data bus width
+    pub const MWID: Field = Field::new(4, 2);
+    /// Number of internal banks
+    pub const NB: Field = Field::new(6, 1);
+    /// CAS latency
+    pub const CAS: Field = Field::new(7, 2);
+    /// Write protection
+    pub const WP: Field = Field::new(9, 1);
+    /// SD clock divider
+    pub const SDCLK: Field = Field::new(10, 2);
+    /// Read burst
+    pub const RBURST: Field = Field::new(12, 1);
+    /// Read pipe delay
+    pub const RPIPE: Field = Field::new(13, 2);
+}
+
+/// Fields of SDTR1 and SDTR2. Each field is the delay in SD clock
+/// cycles, minus 1
+pub mod sdtr {
+    use super::Field;
+    /// Load mode register to active
+    pub const TMRD: Field = Field::new(0, 4);
+    /// Exit self-refresh delay
+    pub const TXSR: Field = Field::new(4, 4);
+    /// Self refresh time
+    pub const TRAS: Field = Field::new(8, 4);
+    /// Row cycle delay
+    pub const TRC: Field = Field::new(12, 4);
+    /// Recovery delay
+    pub const TWR: Field = Field::new(16, 4);
+    /// Row precharge delay
+    pub const TRP: Field = Field::new(20, 4);
+    /// Row to column delay
+    pub const TRCD: Field = Field::new(24, 4);
+}
+
+/// Fields of SDCMR
+pub mod sdcmr {
+    use super::Field;
+    /// Command mode
+    pub const MODE: Field = Field::new(0, 3);
+    /// Command target bank 2
+    pub const CTB2: Field = Field::new(3, 1);
+    /// Command target bank 1
+    pub const CTB1: Field = Field::new(4, 1);
+    /// Number of auto-refresh
+    pub const NRFS: Field = Field::new(5, 4);
+    /// Mode register definition
+    pub const MRD: Field = Field::new(9, 14);
+}
+
+/// Fields of SDRTR
+pub mod sdrtr {
+    use super::Field;
+    /// Clear refresh error flag
+    pub const CRE: Field = Field::new(0, 1);
+    /// Refresh timer count
+    pub const COUNT: Field = Field::new(1, 13);
+    /// Refresh error interrupt enable
+    pub const REIE: Field = Field::new(14, 1);
+}
+
+/// Fields of SDSR
+pub mod sdsr {
+    use super::Field;
+    /// Refresh error flag
+    pub const RE: Field = Field::new(0, 1);
+    /// Status mode for SDRAM bank 1
+    pub const MODES1: Field = Field::new(1, 2);
+    /// Status mode for SDRAM bank 2
+    pub const MODES2: Field = Field::new(3, 2);
+}
+
+/// Read and write access to the FMC registers
+pub trait FmcRegisters {
+    /// Read a register
+    fn read(&self, register: FmcRegister) -> u32;
+
+    /// Write a register
+    ///
+    /// # Safety
+    ///
+    /// Changing the FMC configuration can change how external memory
+    /// is accessed. `value` must be valid for `register`.
+    unsafe fn write(&self, register: FmcRegister, value: u32);
+
+    /// Read a register, then write the value returned by `f`
+    ///
+    /// # Safety
+    ///
+    /// See [`write`](#tymethod.write).
+    unsafe fn modify<F>(&self, register: FmcRegister, f: F)
+    where
+        F: FnOnce(u32) -> u32,
+    {
+        self.write(register, f(self.read(register)));
+    }
+}
+
+impl FmcRegisters for stm32::fmc::RegisterBlock {
+    fn read(&self, register: FmcRegister) -> u32 {
+        use FmcRegister::*;
+
+        match register {
+            Bcr1 => self.bcr1.read().bits(),
+            Sdcr1 => self.sdbank1().sdcr.read().bits(),
+            Sdcr2 => self.sdbank2().sdcr.read().bits(),
+            Sdtr1 => self.sdbank1().sdtr.read().bits(),
+            Sdtr2 => self.sdbank2().sdtr.read().bits(),
+            Sdcmr => self.sdcmr.read().bits(),
+            Sdrtr => self.sdrtr.read().bits(),
+            Sdsr => self.sdsr.read().bits(),
+        }
+    }
+
+    unsafe fn write(&self, register: FmcRegister, value: u32) {
+        use FmcRegister::*;
+
+        match register {
+            Bcr1 => self.bcr1.write(|w| w.bits(value)),
+            Sdcr1 => self.sdbank1().sdcr.write(|w| w.bits(value)),
+            Sdcr2 => self.sdbank2().sdcr.write(|w| w.bits(value)),
+            Sdtr1 => self.sdbank1().sdtr.write(|w| w.bits(value)),
+            Sdtr2 => self.sdbank2().sdtr.write(|w| w.bits(value)),
+            Sdcmr => self.sdcmr.write(|w| w.bits(value)),
+            Sdrtr => self.sdrtr.write(|w| w.bits(value)),
+            // Read-only
+            Sdsr => {}
+        }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+/// Maximum number of writes recorded by [`FakeFmc`](struct.FakeFmc.html)
+pub const FAKE_FMC_WRITES: usize = 64;
+
+#[cfg(any(test, feature = "std"))]
+/// In-memory model of the FMC registers
+///
+/// Registers start at their reset values. Writes are recorded in
+/// order, so that the sequence of register values written by the
+/// driver can be checked. Only the behaviour that the driver depends
+/// on is modelled:
+///
+/// * Mode commands written to SDCMR change the modes in SDSR
+/// * Writing CRE in SDRTR clears the refresh error flag in SDSR
+/// * SDSR is read-only
+#[derive(Debug)]
+pub struct FakeFmc {
+    registers: [Cell<u32>; 8],
+    writes: Cell<[(FmcRegister, u32); FAKE_FMC_WRITES]>,
+    write_count: Cell<usize>,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl Default for FakeFmc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl FakeFmc {
+    /// New set of FMC registers, at their reset values
+    pub const fn new() -> Self {
+        FakeFmc {
+            registers: [
+                Cell::new(0x0000_30DB), // BCR1
+                Cell::new(0x0000_02D0), // SDCR1
+                Cell::new(0x0000_02D0), // SDCR2
+                Cell::new(0x0FFF_FFFF), // SDTR1
+                Cell::new(0x0FFF_FFFF), // SDTR2
+                Cell::new(0),           // SDCMR
+                Cell::new(0),           // SDRTR
+                Cell::new(0),           // SDSR
+            ],
+            writes: Cell::new([(FmcRegister::Sdsr, 0); FAKE_FMC_WRITES]),
+            write_count: Cell::new(0),
+        }
+    }
+
+    /// Writes to the registers, in order. At most
+    /// [`FAKE_FMC_WRITES`](constant.FAKE_FMC_WRITES.html) writes are
+    /// recorded
+    pub fn writes(&self) -> impl Iterator<Item = (FmcRegister, u32)> {
+        let writes = self.writes.get();
+        (0..self.recorded()).map(move |i| writes[i])
+    }
+
+    /// Number of writes to the registers, including writes that were
+    /// not recorded
+    pub fn write_count(&self) -> usize {
+        self.write_count.get()
+    }
+
+    /// Forget the writes that have been recorded
+    pub fn clear_writes(&self) {
+        self.write_count.set(0);
+    }
+
+    /// Set the refresh error flag, as if a refresh error had occurred
+    pub fn set_refresh_error(&self) {
+        let sdsr = &self.registers[FmcRegister::Sdsr as usize];
+        sdsr.set(sdsr::RE.set(sdsr.get(), 1));
+    }
+
+    fn recorded(&self) -> usize {
+        core::cmp::min(self.write_count.get(), FAKE_FMC_WRITES)
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl FmcRegisters for FakeFmc {
+    fn read(&self, register: FmcRegister) -> u32 {
+        self.registers[register as usize].get()
+    }
+
+    unsafe fn write(&self, register: FmcRegister, value: u32) {
+        let count = self.write_count.get();
+        if count < FAKE_FMC_WRITES {
+            let mut writes = self.writes.get();
+            writes[count] = (register, value);
+            self.writes.set(writes);
+        }
+        self.write_count.set(count + 1);
+
+        let sdsr = &self.registers[FmcRegister::Sdsr as usize];
+        match register {
+            FmcRegister::Sdsr => return,
+            FmcRegister::Sdcmr => {
+                let modes = match sdcmr::MODE.get(value) {
+                    0b000 => Some(0b00), // Normal mode
+                    0b101 => Some(0b01), // Self-refresh
+                    0b110 => Some(0b10), // Power-down
+                    _ => None,
+                };
+                if let Some(modes) = modes {
+                    if sdcmr::CTB1.get(value) != 0 {
+                        sdsr.set(sdsr::MODES1.set(sdsr.get(), modes));
+                    }
+                    if sdcmr::CTB2.get(value) != 0 {
+                        sdsr.set(sdsr::MODES2.set(sdsr.get(), modes));
+                    }
+                }
+            }
+            FmcRegister::Sdrtr if sdrtr::CRE.get(value) != 0 => {
+                sdsr.set(sdsr::RE.set(sdsr.get(), 0));
+            }
+            _ => {}
+        }
+
+        // CRE always reads as zero
+        let value = match register {
+            FmcRegister::Sdrtr => sdrtr::CRE.set(value, 0),
+            _ => value,
+        };
+        self.registers[register as usize].set(value);
+    }
+}
@@ -1,14 +1,21 @@
 //! HAL for external SDRAM
 
 use core::cmp;
-use core::convert::TryInto;
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::interrupt;
 
 use crate::hal::blocking::delay::DelayUs;
 use crate::stm32;
 use stm32h7xx_hal::rcc::{rec, CoreClocks};
 
-use crate::fmc::{Fmc, FmcBank, PinsSdram};
+use crate::region::SdramRegion;
+use crate::registers::{
+    bcr1, sdcmr, sdcr, sdrtr, sdsr, sdtr, FmcRegister, FmcRegisters,
+};
+
+use crate::fmc::{BankMapping, Fmc, FmcBank, PinsSdram};
 
 /// FMC SDRAM Configuration Structure definition
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -26,14 +33,72 @@ pub struct FmcSdramConfiguration {
     /// Enables the SDRAM device to be accessed in write mode.
     pub write_protection: bool,
     /// SDRAM clock divider.
-    pub sd_clock_divide: u8,
+    pub sd_clock_divide: SdClockDivide,
     /// This bit enable the SDRAM controller to anticipate the next read
     pub read_burst: bool,
     /// Delay in system clock cycles on read data path.
     pub read_pipe_delay_cycles: u8,
 }
 
+impl FmcSdramConfiguration {
+    /// Size of the SDRAM in bytes
+    pub const fn size_bytes(&self) -> usize {
+        (1 << (self.row_bits + self.column_bits))
+            * self.internal_banks as usize
+            * (self.memory_data_width / 8) as usize
+    }
+}
+
+/// SD clock divider
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SdClockDivide {
+    /// SD clock = 2 x `fmc_ker_ck` period
+    Div2,
+    /// SD clock = 3 x `fmc_ker_ck` period
+    Div3,
+    /// Select the smallest divider that does not exceed the maximum SD
+    /// clock for the SDRAM
+    Auto,
+}
+
+impl SdClockDivide {
+    /// Select a divider for the kernel clock `fmc_ker_ck_hz`. Returns
+    /// the divider and the resulting SD clock.
+    fn select(
+        self,
+        fmc_ker_ck_hz: u32,
+        max_sd_clock_hz: u32,
+    ) -> Result<(u8, u32), SdramError> {
+        let divide = match self {
+            SdClockDivide::Div2 => 2,
+            SdClockDivide::Div3 => 3,
+            SdClockDivide::Auto => {
+                if fmc_ker_ck_hz / 2 <= max_sd_clock_hz {
+                    2
+                } else {
+                    3
+                }
+            }
+        };
+
+        // Check that the SD clock is acceptable
+        let sd_clock_hz = fmc_ker_ck_hz / divide as u32;
+        if sd_clock_hz > max_sd_clock_hz {
+            return Err(SdramError::SdClockTooFast {
+                actual: sd_clock_hz,
+                max: max_sd_clock_hz,
+            });
+        }
+
+        Ok((divide, sd_clock_hz))
+    }
+}
+
 /// FMC SDRAM Timing parameters structure definition
+///
+/// Timing parameters are given in datasheet units, and converted to
+/// SD clock cycles when the SDRAM is initialised. See
+/// [`SdramTime`](struct.SdramTime.html).
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct FmcSdramTiming {
     /// Time between applying a valid clock and any command other than
@@ -44,38 +109,806 @@ pub struct FmcSdramTiming {
     /// Period between refresh cycles in nanoseconds.
     pub refresh_period_ns: u32,
     /// Delay between a LOAD MODE register command and an ACTIVATE command.
-    pub mode_register_to_active: u8,
+    pub mode_register_to_active: SdramTime,
     /// Delay from releasing self refresh to next command.
-    pub exit_self_refresh: u8,
+    pub exit_self_refresh: SdramTime,
     /// Delay between an ACTIVATE and a PRECHARGE command.
-    pub active_to_precharge: u8,
+    pub active_to_precharge: SdramTime,
     /// Auto refresh command duration.
-    pub row_cycle: u8,
+    pub row_cycle: SdramTime,
     /// Delay between a PRECHARGE command and another command.
-    pub row_precharge: u8,
+    pub row_precharge: SdramTime,
     /// Delay between an ACTIVATE command and READ/WRITE command.
-    pub row_to_column: u8,
+    pub row_to_column: SdramTime,
+}
+
+/// SDRAM timing parameter, as given in the datasheet
+///
+/// The parameter is rounded up to a whole number of SD clock cycles
+/// when the SDRAM is initialised. It is at least `ns` nanoseconds and
+/// at least `cycles` SD clock cycles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SdramTime {
+    /// Minimum time in nanoseconds
+    pub ns: u32,
+    /// Minimum number of SD clock cycles
+    pub cycles: u8,
+}
+
+impl SdramTime {
+    /// Timing parameter in nanoseconds
+    pub const fn ns(ns: u32) -> Self {
+        SdramTime { ns, cycles: 0 }
+    }
+    /// Timing parameter in SD clock cycles, independent of the SD
+    /// clock frequency
+    pub const fn cycles(cycles: u8) -> Self {
+        SdramTime { ns: 0, cycles }
+    }
+    /// Also require a minimum number of SD clock cycles
+    pub const fn min_cycles(self, cycles: u8) -> Self {
+        SdramTime {
+            ns: self.ns,
+            cycles,
+        }
+    }
+    /// Number of SD clock cycles for this parameter at `sd_clock_hz`
+    pub const fn to_cycles(&self, sd_clock_hz: u32) -> u32 {
+        // time (ns) * frequency (hz) / 10^9 = cycles, rounded up
+        let ns_cycles =
+            (self.ns as u64 * sd_clock_hz as u64 + 999_999_999) / 1_000_000_000;
+
+        if ns_cycles > self.cycles as u64 {
+            ns_cycles as u32
+        } else {
+            self.cycles as u32
+        }
+    }
+}
+
+/// Burst length field of the SDRAM mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BurstLength {
+    /// 1 location
+    One = 0b000,
+    /// 2 locations
+    Two = 0b001,
+    /// 4 locations
+    Four = 0b010,
+    /// 8 locations
+    Eight = 0b011,
+    /// Full page. Only valid with sequential bursts
+    FullPage = 0b111,
+}
+
+/// Burst type field of the SDRAM mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BurstType {
+    /// Sequential burst
+    Sequential = 0,
+    /// Interleaved burst
+    Interleaved = 1,
+}
+
+/// Operating mode field of the SDRAM mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperatingMode {
+    /// Standard operation. All other values are reserved
+    Standard = 0b00,
+}
+
+/// Write burst mode field of the SDRAM mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteBurstMode {
+    /// Writes use the programmed burst length
+    Programmed = 0,
+    /// Single location writes
+    Single = 1,
+}
+
+/// SDRAM mode register
+///
+/// Loaded into the SDRAM with a LOAD MODE REGISTER command when it is
+/// initialised. All methods are `const`, so that a mode register can
+/// be built in a [`SdramChip`](trait.SdramChip.html) definition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModeRegister {
+    /// Burst length
+    pub burst_length: BurstLength,
+    /// Burst type
+    pub burst_type: BurstType,
+    /// CAS latency in SD clock cycles. Must be equal to
+    /// `FmcSdramConfiguration::cas_latency`
+    pub cas_latency: u8,
+    /// Operating mode
+    pub operating_mode: OperatingMode,
+    /// Write burst mode
+    pub write_burst_mode: WriteBurstMode,
+}
+
+impl ModeRegister {
+    /// Mode register with a burst length of 1, sequential bursts, CAS
+    /// latency of 3, standard operation and programmed burst length
+    /// writes
+    pub const fn new() -> Self {
+        ModeRegister {
+            burst_length: BurstLength::One,
+            burst_type: BurstType::Sequential,
+            cas_latency: 3,
+            operating_mode: OperatingMode::Standard,
+            write_burst_mode: WriteBurstMode::Programmed,
+        }
+    }
+    /// Set the burst length
+    pub const fn burst_length(self, burst_length: BurstLength) -> Self {
+        ModeRegister {
+            burst_length,
+            ..self
+        }
+    }
+    /// Set the burst type
+    pub const fn burst_type(self, burst_type: BurstType) -> Self {
+        ModeRegister { burst_type, ..self }
+    }
+    /// Set the CAS latency in SD clock cycles
+    pub const fn cas_latency(self, cas_latency: u8) -> Self {
+        ModeRegister {
+            cas_latency,
+            ..self
+        }
+    }
+    /// Set the operating mode
+    pub const fn operating_mode(self, operating_mode: OperatingMode) -> Self {
+        ModeRegister {
+            operating_mode,
+            ..self
+        }
+    }
+    /// Set the write burst mode
+    pub const fn write_burst_mode(
+        self,
+        write_burst_mode: WriteBurstMode,
+    ) -> Self {
+        ModeRegister {
+            write_burst_mode,
+            ..self
+        }
+    }
+    /// Value of the mode register
+    pub const fn bits(&self) -> u16 {
+        (self.burst_length as u16)
+            | (self.burst_type as u16) << 3
+            | (self.cas_latency as u16 & 0b111) << 4
+            | (self.operating_mode as u16) << 7
+            | (self.write_burst_mode as u16) << 9
+    }
+    /// Check that the mode register agrees with the SDRAM controller
+    /// configuration
+    pub const fn check(
+        &self,
+        config: &FmcSdramConfiguration,
+    ) -> Result<(), SdramError> {
+        // CAS latency 1 ~ 3 cycles
+        if self.cas_latency < 1 || self.cas_latency > 3 {
+            return Err(SdramError::InvalidCasLatency(self.cas_latency));
+        }
+        if self.cas_latency != config.cas_latency {
+            return Err(SdramError::CasLatencyMismatch {
+                mode_register: self.cas_latency,
+                config: config.cas_latency,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for ModeRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Partial array self-refresh field of the extended mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartialArraySelfRefresh {
+    /// Refresh all banks
+    Full = 0b000,
+    /// Refresh banks 0 and 1
+    Half = 0b001,
+    /// Refresh bank 0
+    Quarter = 0b010,
+    /// Refresh half of bank 0
+    Eighth = 0b101,
+    /// Refresh a quarter of bank 0
+    Sixteenth = 0b110,
+}
+
+/// Temperature compensated self-refresh field of the extended mode
+/// register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TemperatureCompensatedSelfRefresh {
+    /// Maximum case temperature 70°C
+    Max70C = 0b00,
+    /// Maximum case temperature 45°C
+    Max45C = 0b01,
+    /// Maximum case temperature 15°C
+    Max15C = 0b10,
+    /// Maximum case temperature 85°C
+    Max85C = 0b11,
+}
+
+/// Drive strength field of the extended mode register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DriveStrength {
+    /// Full drive strength
+    Full = 0b00,
+    /// Half drive strength
+    Half = 0b01,
+    /// Quarter drive strength
+    Quarter = 0b10,
+    /// One-eighth drive strength
+    Eighth = 0b11,
+}
+
+/// Extended mode register of a mobile (low-power) SDRAM
+///
+/// Loaded into the SDRAM with a LOAD MODE REGISTER command with BA1
+/// set, after the mode register.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExtendedModeRegister {
+    /// Part of the array that is refreshed in self-refresh mode
+    pub partial_array_self_refresh: PartialArraySelfRefresh,
+    /// Temperature compensated self-refresh
+    pub temperature_compensated_self_refresh: TemperatureCompensatedSelfRefresh,
+    /// Output drive strength
+    pub drive_strength: DriveStrength,
+}
+
+impl ExtendedModeRegister {
+    /// Extended mode register with full array self-refresh, 70°C
+    /// temperature compensated self-refresh and full drive strength
+    pub const fn new() -> Self {
+        ExtendedModeRegister {
+            partial_array_self_refresh: PartialArraySelfRefresh::Full,
+            temperature_compensated_self_refresh:
+                TemperatureCompensatedSelfRefresh::Max70C,
+            drive_strength: DriveStrength::Full,
+        }
+    }
+    /// Set the partial array self-refresh
+    pub const fn partial_array_self_refresh(
+        self,
+        partial_array_self_refresh: PartialArraySelfRefresh,
+    ) -> Self {
+        ExtendedModeRegister {
+            partial_array_self_refresh,
+            ..self
+        }
+    }
+    /// Set the temperature compensated self-refresh
+    pub const fn temperature_compensated_self_refresh(
+        self,
+        temperature_compensated_self_refresh: TemperatureCompensatedSelfRefresh,
+    ) -> Self {
+        ExtendedModeRegister {
+            temperature_compensated_self_refresh,
+            ..self
+        }
+    }
+    /// Set the drive strength
+    pub const fn drive_strength(self, drive_strength: DriveStrength) -> Self {
+        ExtendedModeRegister {
+            drive_strength,
+            ..self
+        }
+    }
+    /// Value of the extended mode register, on address lines A0 ~ A11
+    pub const fn bits(&self) -> u16 {
+        (self.partial_array_self_refresh as u16)
+            | (self.temperature_compensated_self_refresh as u16) << 3
+            | (self.drive_strength as u16) << 5
+    }
+    /// Value to write to the MRD field of the SDCMR register for an
+    /// SDRAM with `row_bits` address lines
+    ///
+    /// The FMC drives MRD onto the address lines followed by the
+    /// internal bank address lines, so BA1 is bit `row_bits + 1` of
+    /// MRD. MRD is 14 bits wide, so `row_bits` must be 12 or less.
+    pub const fn load_value(&self, row_bits: u8) -> Result<u16, SdramError> {
+        if row_bits > 12 {
+            return Err(SdramError::ExtendedModeRegisterRowBits(row_bits));
+        }
+        Ok(self.bits() | 1 << (row_bits + 1))
+    }
+}
+
+impl Default for ExtendedModeRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// SDRAM timing parameters in SD clock cycles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SdramCycles {
+    pub(crate) mode_register_to_active: u8,
+    pub(crate) exit_self_refresh: u8,
+    pub(crate) active_to_precharge: u8,
+    pub(crate) row_cycle: u8,
+    pub(crate) row_precharge: u8,
+    pub(crate) row_to_column: u8,
+    pub(crate) write_recovery: u8,
+}
+
+/// Errors that can occur when initialising an SDRAM
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SdramError {
+    /// The FMC kernel clock `fmc_ker_ck` is not running
+    KernelClockStopped,
+    /// The SD clock is faster than the maximum SD clock for this SDRAM
+    SdClockTooFast {
+        /// SD clock that would result from the current `fmc_ker_ck`. For
+        /// [`SdClockDivide::Auto`](enum.SdClockDivide.html) this is the
+        /// slowest SD clock that can be achieved
+        actual: u32,
+        /// Maximum SD clock in `IC::TIMING`
+        max: u32,
+    },
+    /// The refresh counter does not fit in the SDRTR register (41 ~ 8191)
+    RefreshCounterOutOfRange(u64),
+    /// The startup delay is longer than 255µs
+    StartupDelayOutOfRange(u32),
+    /// SDRAM controller bank is not 1 or 2
+    InvalidExternalBank(u8),
+    /// CAS latency is not 1 ~ 3 cycles
+    InvalidCasLatency(u8),
+    /// Number of row bits is not 11 ~ 13
+    InvalidRowBits(u8),
+    /// Number of column bits is not 8 ~ 11
+    InvalidColumnBits(u8),
+    /// Read pipe delay is not 0 ~ 2 cycles
+    InvalidReadPipeDelay(u8),
+    /// Number of internal banks is not 2 or 4
+    InvalidInternalBanks(u8),
+    /// Memory data width is not 8, 16 or 32 bits
+    InvalidDataWidth(u8),
+    /// The CAS latency in the mode register is different to the CAS
+    /// latency in the SDRAM controller configuration
+    CasLatencyMismatch {
+        /// CAS latency in `IC::MODE_REGISTER`
+        mode_register: u8,
+        /// CAS latency in `IC::CONFIG`
+        config: u8,
+    },
+    /// The extended mode register cannot be loaded for an SDRAM with
+    /// more than 12 row bits
+    ExtendedModeRegisterRowBits(u8),
+    /// The SDRAM does not have an extended mode register
+    NoExtendedModeRegister,
+    /// The SD clock dividers for two SDRAMs sharing the controller are
+    /// different
+    IncompatibleClockDivide,
+    /// The SDRAM did not enter the requested mode
+    ModeChangeTimeout,
+    /// The data width of the pins is different to the data width of the
+    /// SDRAM
+    PinsDataWidthMismatch {
+        /// Data width of the pins
+        pins: u8,
+        /// Data width of the SDRAM
+        sdram: u8,
+    },
+    /// There are not enough address lines in the pins to access all the
+    /// rows and columns of the SDRAM
+    NotEnoughAddressPins {
+        /// Number of address lines in the pins
+        pins: u8,
+        /// Number of address lines needed by the SDRAM
+        sdram: u8,
+    },
+    /// There are not enough bank address lines in the pins to access
+    /// all the internal banks of the SDRAM
+    NotEnoughBankPins {
+        /// Number of internal banks that the pins can address
+        pins: u8,
+        /// Number of internal banks in the SDRAM
+        sdram: u8,
+    },
+    /// A timing parameter is not 1 ~ 16 cycles. Contains the name of
+    /// the parameter
+    TimingOutOfRange(&'static str),
+}
+
+/// Check that a timing parameter fits in the SDTR register
+const fn check_cycles(
+    cycles: u32,
+    name: &'static str,
+) -> Result<u8, SdramError> {
+    if cycles >= 1 && cycles <= 16 {
+        Ok(cycles as u8)
+    } else {
+        Err(SdramError::TimingOutOfRange(name))
+    }
+}
+
+impl FmcSdramTiming {
+    /// Round up each timing parameter to a whole number of SD clock
+    /// cycles at `sd_clock_hz`
+    pub(crate) const fn cycles(
+        &self,
+        sd_clock_hz: u32,
+    ) -> Result<SdramCycles, SdramError> {
+        let mode_register_to_active = const_try!(check_cycles(
+            self.mode_register_to_active.to_cycles(sd_clock_hz),
+            "mode_register_to_active"
+        ));
+        let exit_self_refresh = const_try!(check_cycles(
+            self.exit_self_refresh.to_cycles(sd_clock_hz),
+            "exit_self_refresh"
+        ));
+        let active_to_precharge = const_try!(check_cycles(
+            self.active_to_precharge.to_cycles(sd_clock_hz),
+            "active_to_precharge"
+        ));
+        let row_cycle = const_try!(check_cycles(
+            self.row_cycle.to_cycles(sd_clock_hz),
+            "row_cycle"
+        ));
+        let row_precharge = const_try!(check_cycles(
+            self.row_precharge.to_cycles(sd_clock_hz),
+            "row_precharge"
+        ));
+        let row_to_column = const_try!(check_cycles(
+            self.row_to_column.to_cycles(sd_clock_hz),
+            "row_to_column"
+        ));
+
+        SdramCycles {
+            mode_register_to_active,
+            exit_self_refresh,
+            active_to_precharge,
+            row_cycle,
+            row_precharge,
+            row_to_column,
+            write_recovery: 0,
+        }
+        .derive_write_recovery()
+    }
+
+    /// Value for the refresh rate counter at `sd_clock_hz`
+    fn refresh_counter(&self, sd_clock_hz: u32) -> Result<u16, SdramError> {
+        // period (ns) * frequency (hz) / 10^9 = count
+        let refresh_counter_top = ((self.refresh_period_ns as u64
+            * sd_clock_hz as u64)
+            / 1_000_000_000)
+            .saturating_sub(20);
+
+        if (41..(1 << 13)).contains(&refresh_counter_top) {
+            Ok(refresh_counter_top as u16)
+        } else {
+            Err(SdramError::RefreshCounterOutOfRange(refresh_counter_top))
+        }
+    }
+
+    /// SDRAM powerup delay in microseconds. Checked by `validate`
+    const fn startup_delay_us(&self) -> u32 {
+        (self.startup_delay_ns + 999) / 1000
+    }
+}
+
+impl SdramCycles {
+    /// Derive the write recovery time from the other timings
+    const fn derive_write_recovery(self) -> Result<Self, SdramError> {
+        // Write recovery - Self refresh
+        let write_recovery_self_refresh =
+            self.active_to_precharge.saturating_sub(self.row_to_column);
+        // Write recovery - WRITE command to PRECHARGE command
+        let write_recovery_row_cycle = self
+            .row_cycle
+            .saturating_sub(self.row_to_column)
+            .saturating_sub(self.row_precharge);
+        let write_recovery =
+            if write_recovery_self_refresh > write_recovery_row_cycle {
+                write_recovery_self_refresh
+            } else {
+                write_recovery_row_cycle
+            };
+        // At slow clocks both can round to 0 cycles, but TWR is at least
+        // 1 cycle
+        let write_recovery = const_try!(check_cycles(
+            if write_recovery > 1 {
+                write_recovery as u32
+            } else {
+                1
+            },
+            "write_recovery"
+        ));
+
+        Ok(SdramCycles {
+            write_recovery,
+            ..self
+        })
+    }
+
+    /// Use the row cycle, row precharge and write recovery delays that
+    /// are common to both SDRAM banks. These are the longest delays of
+    /// either SDRAM
+    fn common(
+        cycles1: SdramCycles,
+        cycles2: SdramCycles,
+    ) -> Result<(SdramCycles, SdramCycles), SdramError> {
+        let row_cycle = cmp::max(cycles1.row_cycle, cycles2.row_cycle);
+        let row_precharge =
+            cmp::max(cycles1.row_precharge, cycles2.row_precharge);
+        let cycles1 = SdramCycles {
+            row_cycle,
+            row_precharge,
+            ..cycles1
+        }
+        .derive_write_recovery()?;
+        let cycles2 = SdramCycles {
+            row_cycle,
+            row_precharge,
+            ..cycles2
+        }
+        .derive_write_recovery()?;
+
+        // TWR must be the same in SDTR1 and SDTR2
+        let write_recovery =
+            cmp::max(cycles1.write_recovery, cycles2.write_recovery);
+        Ok((
+            SdramCycles {
+                write_recovery,
+                ..cycles1
+            },
+            SdramCycles {
+                write_recovery,
+                ..cycles2
+            },
+        ))
+    }
+}
+
+/// Check that an SDRAM configuration and timing can be achieved by
+/// the H7 FMC controller.
+///
+/// This is a `const fn`, so it can be evaluated at compile time. See
+/// [`SdramChip::VALID`](trait.SdramChip.html#associatedconstant.VALID).
+pub const fn validate(
+    config: &FmcSdramConfiguration,
+    timing: &FmcSdramTiming,
+) -> Result<(), SdramError> {
+    // Features ---- SDCR REGISTER
+
+    // CAS latency 1 ~ 3 cycles
+    if config.cas_latency < 1 || config.cas_latency > 3 {
+        return Err(SdramError::InvalidCasLatency(config.cas_latency));
+    }
+    // Row Bits: 11 ~ 13
+    if config.row_bits < 11 || config.row_bits > 13 {
+        return Err(SdramError::InvalidRowBits(config.row_bits));
+    }
+    // Column bits: 8 ~ 11
+    if config.column_bits < 8 || config.column_bits > 11 {
+        return Err(SdramError::InvalidColumnBits(config.column_bits));
+    }
+    // Read Pipe Delay Cycles 0 ~ 2
+    if config.read_pipe_delay_cycles > 2 {
+        return Err(SdramError::InvalidReadPipeDelay(
+            config.read_pipe_delay_cycles,
+        ));
+    }
+    // Internal banks 2 or 4
+    if config.internal_banks != 2 && config.internal_banks != 4 {
+        return Err(SdramError::InvalidInternalBanks(config.internal_banks));
+    }
+    // Memory width 8, 16 or 32 bits
+    match config.memory_data_width {
+        8 | 16 | 32 => {}
+        w => return Err(SdramError::InvalidDataWidth(w)),
+    }
+
+    // Timing ---- SDTR REGISTER
+
+    // Startup delay is passed to `DelayUs<u8>`
+    if timing.startup_delay_us() > 255 {
+        return Err(SdramError::StartupDelayOutOfRange(
+            timing.startup_delay_us(),
+        ));
+    }
+
+    // All timings are 1 ~ 16 cycles. The number of cycles is largest
+    // at the maximum SD clock. At slower clocks a non-zero time is still
+    // at least 1 cycle, and the write recovery is clamped to 1 cycle, so
+    // this also holds for slower clocks
+    match timing.cycles(timing.max_sd_clock_hz) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Like [`validate`](fn.validate.html) and
+/// [`ModeRegister::check`](struct.ModeRegister.html#method.check), but
+/// panics with a description of the problem. When evaluated in a
+/// const context this causes a compile error.
+const fn assert_valid(
+    mode_register: &ModeRegister,
+    extended_mode_register: &Option<ExtendedModeRegister>,
+    config: &FmcSdramConfiguration,
+    timing: &FmcSdramTiming,
+) {
+    use SdramError::*;
+
+    let result = match validate(config, timing) {
+        Ok(()) => mode_register.check(config),
+        Err(e) => Err(e),
+    };
+    let result = match (result, extended_mode_register) {
+        (Ok(()), Some(emr)) => match emr.load_value(config.row_bits) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        },
+        (result, _) => result,
+    };
+    match result {
+        Ok(()) => {}
+        Err(InvalidCasLatency(_)) => panic!("CAS latency must be 1 ~ 3"),
+        Err(InvalidRowBits(_)) => panic!("Row bits must be 11 ~ 13"),
+        Err(InvalidColumnBits(_)) => panic!("Column bits must be 8 ~ 11"),
+        Err(InvalidReadPipeDelay(_)) => {
+            panic!("Read pipe delay must be 0 ~ 2 cycles")
+        }
+        Err(InvalidInternalBanks(_)) => {
+            panic!("Number of internal banks must be 2 or 4")
+        }
+        Err(InvalidDataWidth(_)) => {
+            panic!("Memory data width must be 8, 16 or 32 bits")
+        }
+        Err(StartupDelayOutOfRange(_)) => {
+            panic!("Startup delay must be 255µs or less")
+        }
+        Err(TimingOutOfRange(_)) => {
+            panic!("SDRAM timings must be 1 ~ 16 cycles")
+        }
+        Err(CasLatencyMismatch { .. }) => {
+            panic!("Mode register CAS latency must match CONFIG")
+        }
+        Err(ExtendedModeRegisterRowBits(_)) => {
+            panic!("Extended mode register requires 12 row bits or less")
+        }
+        Err(_) => panic!("Invalid SDRAM chip definition"),
+    }
 }
 
+/// Definition of an SDRAM chip
 pub trait SdramChip {
-    const MODE_REGISTER: u16;
+    /// Mode register
+    const MODE_REGISTER: ModeRegister;
+    /// Extended mode register, for mobile (low-power) SDRAMs
+    const EXTENDED_MODE_REGISTER: Option<ExtendedModeRegister> = None;
+    /// SDRAM controller configuration
     const CONFIG: FmcSdramConfiguration;
+    /// Timing parameters
     const TIMING: FmcSdramTiming;
+
+    /// Evaluating this constant checks `CONFIG` and `TIMING` with
+    /// [`validate`](fn.validate.html), and `MODE_REGISTER` with
+    /// [`ModeRegister::check`](struct.ModeRegister.html#method.check).
+    /// An invalid chip definition fails to compile.
+    ///
+    /// This is evaluated when an [`Sdram`](struct.Sdram.html) is
+    /// constructed for the chip, and should not be overridden.
+    const VALID: () = assert_valid(
+        &Self::MODE_REGISTER,
+        &Self::EXTENDED_MODE_REGISTER,
+        &Self::CONFIG,
+        &Self::TIMING,
+    );
+}
+
+/// Description of an SDRAM chip, given at runtime
+///
+/// This can be used with
+/// [`Sdram::new_dynamic`](struct.Sdram.html#method.new_dynamic) when
+/// the SDRAM is not known at compile time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SdramDescriptor {
+    /// Mode register
+    pub mode_register: ModeRegister,
+    /// Extended mode register, for mobile (low-power) SDRAMs
+    pub extended_mode_register: Option<ExtendedModeRegister>,
+    /// SDRAM controller configuration
+    pub config: FmcSdramConfiguration,
+    /// Timing parameters
+    pub timing: FmcSdramTiming,
+}
+
+impl SdramDescriptor {
+    /// Descriptor for an SDRAM chip that is known at compile time
+    pub fn from_chip<IC: SdramChip>() -> Self {
+        SdramDescriptor {
+            mode_register: IC::MODE_REGISTER,
+            extended_mode_register: IC::EXTENDED_MODE_REGISTER,
+            config: IC::CONFIG,
+            timing: IC::TIMING,
+        }
+    }
+
+    /// Check the descriptor. This makes the same checks as
+    /// [`SdramChip::VALID`](trait.SdramChip.html#associatedconstant.VALID),
+    /// but returns an error instead.
+    pub const fn validate(&self) -> Result<(), SdramError> {
+        const_try!(validate(&self.config, &self.timing));
+        const_try!(self.mode_register.check(&self.config));
+        if let Some(emr) = self.extended_mode_register {
+            let _ = const_try!(emr.load_value(self.config.row_bits));
+        }
+        Ok(())
+    }
 }
 
+/// Type states for the SDRAM controller
+pub mod state {
+    /// The SDRAM has not been initialised
+    #[derive(Clone, Copy, Debug)]
+    pub struct Unconfigured;
+    /// The SDRAM is in normal mode and can be accessed
+    #[derive(Clone, Copy, Debug)]
+    pub struct Ready;
+    /// The SDRAM is in self-refresh mode. The contents are retained,
+    /// but the SDRAM cannot be accessed
+    #[derive(Clone, Copy, Debug)]
+    pub struct SelfRefresh;
+    /// The SDRAM is in power-down mode
+    #[derive(Clone, Copy, Debug)]
+    pub struct PowerDown;
+}
+use state::*;
+
 /// SDRAM Controller
+///
+/// The type state `STATE` is one of the types in
+/// [`state`](state/index.html). A new controller is
+/// [`Unconfigured`](state/struct.Unconfigured.html), and becomes
+/// [`Ready`](state/struct.Ready.html) when it is initialised.
 #[allow(missing_debug_implementations)]
-pub struct Sdram<IC, PINS> {
+pub struct Sdram<IC, PINS, STATE = Ready> {
     mem: Fmc,
     /// FMC pins
-    _pins: PhantomData<PINS>,
+    pins: PINS,
     /// Parameters for the SDRAM IC
+    chip: SdramDescriptor,
     _chip: PhantomData<IC>,
+    /// SDRAM mode
+    _state: PhantomData<STATE>,
+}
+
+/// SDRAM Controller for two SDRAMs, one on each SDRAM bank
+///
+/// Some settings are common between both banks: the SD clock, the read
+/// pipe delay, read burst, the row cycle, row precharge and write
+/// recovery delays, and the refresh rate. These are chosen to meet the
+/// requirements of both SDRAMs.
+///
+/// As for [`Sdram`](struct.Sdram.html), a new controller is
+/// [`Unconfigured`](state/struct.Unconfigured.html), and becomes
+/// [`Ready`](state/struct.Ready.html) when it is initialised.
+#[allow(missing_debug_implementations)]
+pub struct SdramDual<IC1, PINS1, IC2, PINS2, STATE = Ready> {
+    mem: Fmc,
+    /// FMC pins
+    pins: (PINS1, PINS2),
+    /// Extended mode registers, as last loaded into the SDRAMs
+    extended_mode_registers:
+        (Option<ExtendedModeRegister>, Option<ExtendedModeRegister>),
+    /// Parameters for the SDRAM ICs
+    _chip: PhantomData<(IC1, IC2)>,
+    /// SDRAM mode
+    _state: PhantomData<STATE>,
 }
 
 /// SDRAM Commands
 #[derive(Clone, Copy, Debug, PartialEq)]
-#[allow(unused)]
 enum SdramCommand {
     NormalMode,
     ClkEnable,
@@ -85,26 +918,162 @@ enum SdramCommand {
     Selfrefresh,
     Powerdown,
 }
+/// SDRAM modes, as reported in SDSR
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SdramMode {
+    Normal = 0b00,
+    SelfRefresh = 0b01,
+    PowerDown = 0b10,
+}
+
+/// Maximum number of times to poll SDSR for a mode change
+const MODE_CHANGE_TIMEOUT: u32 = 100_000;
+
+/// Number of refresh errors that have been cleared
+static REFRESH_ERRORS: AtomicU32 = AtomicU32::new(0);
+
 /// Target bank for SDRAM commands
 #[derive(Clone, Copy, Debug, PartialEq)]
-#[allow(unused)]
 enum SdramTargetBank {
     Bank1,
     Bank2,
     Both,
 }
+impl SdramTargetBank {
+    /// SDRAM controller bank for a set of pins
+    fn from_pins<PINS: PinsSdram<stm32::FMC>>() -> Result<Self, SdramError> {
+        Self::from_external_bank(PINS::EXTERNAL_BANK)
+    }
+
+    /// SDRAM controller bank 1 or 2
+    fn from_external_bank(external_bank: u8) -> Result<Self, SdramError> {
+        match external_bank {
+            1 => Ok(SdramTargetBank::Bank1),
+            2 => Ok(SdramTargetBank::Bank2),
+            b => Err(SdramError::InvalidExternalBank(b)),
+        }
+    }
+
+    /// Base address of the memory-mapped SDRAM
+    fn ptr(self, bank_mapping: BankMapping) -> *mut u32 {
+        match (self, bank_mapping) {
+            (SdramTargetBank::Bank1, BankMapping::Swapped) => {
+                FmcBank::Bank1.ptr()
+            }
+            (SdramTargetBank::Bank1, _) => FmcBank::Bank5.ptr(),
+            (_, BankMapping::Default) => FmcBank::Bank6.ptr(),
+            (_, _) => FmcBank::Bank2.ptr(),
+        }
+    }
+}
+
+/// Checks of a set of pins `PINS` against an SDRAM `IC`, evaluated at
+/// compile time
+struct PinsCheck<IC, PINS>(PhantomData<(IC, PINS)>);
+
+impl<IC: SdramChip, PINS: PinsSdram<stm32::FMC>> PinsCheck<IC, PINS> {
+    /// The data width of the pins must match the SDRAM
+    const DATA_WIDTH: () = assert!(
+        PINS::DATA_WIDTH == IC::CONFIG.memory_data_width,
+        "Data width of the pins does not match the SDRAM data width"
+    );
+    /// The pins must be on SDRAM bank 1
+    const BANK1: () = assert!(
+        PINS::EXTERNAL_BANK == 1,
+        "The pins for the first SDRAM must be on SDRAM bank 1"
+    );
+    /// The pins must be on SDRAM bank 2
+    const BANK2: () = assert!(
+        PINS::EXTERNAL_BANK == 2,
+        "The pins for the second SDRAM must be on SDRAM bank 2"
+    );
+}
+
+/// Check that `PINS` can access the whole of the SDRAM `IC`
+///
+/// # Panics
+///
+/// * Panics if there are not enough address lines in `PINS` to
+/// access the whole SDRAM.
+///
+/// * Panics if there are not enough bank address lines in `PINS`
+/// to access the whole SDRAM.
+pub(crate) fn check_pins<IC: SdramChip, PINS: PinsSdram<stm32::FMC>>() {
+    // Check the data width at compile time
+    let () = PinsCheck::<IC, PINS>::DATA_WIDTH;
+
+    match check_pin_lines::<PINS>(&IC::CONFIG) {
+        Ok(()) => {}
+        Err(SdramError::NotEnoughBankPins { .. }) => {
+            panic!("Not enough bank address pins to access all internal banks")
+        }
+        Err(_) => {
+            panic!(
+                "Not enough address pins to access all SDRAM rows and columns"
+            )
+        }
+    }
+}
+
+/// Check that `PINS` has the data width of an SDRAM with configuration
+/// `config`, and enough address and bank address lines to access it
+pub(crate) fn check_pins_dynamic<PINS: PinsSdram<stm32::FMC>>(
+    config: &FmcSdramConfiguration,
+) -> Result<(), SdramError> {
+    if PINS::DATA_WIDTH != config.memory_data_width {
+        return Err(SdramError::PinsDataWidthMismatch {
+            pins: PINS::DATA_WIDTH,
+            sdram: config.memory_data_width,
+        });
+    }
+
+    check_pin_lines::<PINS>(config)
+}
+
+/// Check that `PINS` has enough address and bank address lines for
+/// an SDRAM with configuration `config`
+fn check_pin_lines<PINS: PinsSdram<stm32::FMC>>(
+    config: &FmcSdramConfiguration,
+) -> Result<(), SdramError> {
+    // A10 is the auto-precharge flag during reads and writes, so
+    // column bit 10 is on A11
+    let column_lines = if config.column_bits > 10 {
+        config.column_bits + 1
+    } else {
+        config.column_bits
+    };
+    let address_lines = cmp::max(config.row_bits, column_lines);
+    if PINS::ADDRESS_LINES < address_lines {
+        return Err(SdramError::NotEnoughAddressPins {
+            pins: PINS::ADDRESS_LINES,
+            sdram: address_lines,
+        });
+    }
+    if PINS::NUMBER_INTERNAL_BANKS < config.internal_banks {
+        return Err(SdramError::NotEnoughBankPins {
+            pins: PINS::NUMBER_INTERNAL_BANKS,
+            sdram: config.internal_banks,
+        });
+    }
+    Ok(())
+}
 
-impl<IC, PINS> Sdram<IC, PINS>
+impl<IC, PINS> Sdram<IC, PINS, Unconfigured>
 where
     IC: SdramChip,
     PINS: PinsSdram<stm32::FMC>,
 {
     /// New SDRAM instance
     ///
-    /// `_pins` must be a set of pins connecting to an SDRAM on the
+    /// `pins` must be a set of pins connecting to an SDRAM on the
     /// FMC controller. This is currently implemented for the types
-    /// [`PinsSdramBank1`](struct.PinsSdramBank1.html) and
-    /// [`PinsSdramBank2`](struct.PinsSdramBank2.html)
+    /// [`PinsSdramBank1`](struct.PinsSdramBank1.html),
+    /// [`PinsSdramBank2`](struct.PinsSdramBank2.html),
+    /// [`PinsSdramBank1A10Ba1`](struct.PinsSdramBank1A10Ba1.html) and
+    /// [`PinsSdramBank2A10Ba1`](struct.PinsSdramBank2A10Ba1.html)
+    ///
+    /// Fails to compile if the data width of `PINS` is different to
+    /// the data width of the SDRAM.
     ///
     /// # Panics
     ///
@@ -116,35 +1085,31 @@ where
     pub fn new(
         fmc: stm32::FMC,
         rec_fmc: rec::Fmc,
-        _pins: PINS,
+        pins: PINS,
         _chip: IC,
     ) -> Self {
-        assert!(
-            PINS::ADDRESS_LINES >= IC::CONFIG.row_bits,
-            "Not enough address pins to access all SDRAM rows"
-        );
-        assert!(
-            PINS::ADDRESS_LINES >= IC::CONFIG.column_bits,
-            "Not enough address pins to access all SDRAM colums"
-        );
-        assert!(
-            PINS::NUMBER_INTERNAL_BANKS >= IC::CONFIG.internal_banks,
-            "Not enough bank address pins to access all internal banks"
-        );
+        // Check the chip definition at compile time
+        let () = IC::VALID;
+
+        check_pins::<IC, PINS>();
 
         Sdram {
             mem: Fmc::new(fmc, rec_fmc),
-            _pins: PhantomData,
+            pins,
+            chip: SdramDescriptor::from_chip::<IC>(),
             _chip: PhantomData,
+            _state: PhantomData,
         }
     }
 
     /// New SDRAM instance
     ///
-    /// `_pins` must be a set of pins connecting to an SDRAM on the
+    /// `pins` must be a set of pins connecting to an SDRAM on the
     /// FMC controller. This is currently implemented for the types
-    /// [`PinsSdramBank1`](struct.PinsSdramBank1.html) and
-    /// [`PinsSdramBank2`](struct.PinsSdramBank2.html)
+    /// [`PinsSdramBank1`](struct.PinsSdramBank1.html),
+    /// [`PinsSdramBank2`](struct.PinsSdramBank2.html),
+    /// [`PinsSdramBank1A10Ba1`](struct.PinsSdramBank1A10Ba1.html) and
+    /// [`PinsSdramBank2A10Ba1`](struct.PinsSdramBank2A10Ba1.html)
     ///
     /// # Safety
     ///
@@ -154,208 +1119,1089 @@ where
     pub unsafe fn new_unchecked(
         fmc: stm32::FMC,
         rec_fmc: rec::Fmc,
-        _pins: PINS,
+        pins: PINS,
         _chip: IC,
     ) -> Self {
+        // Check the chip definition at compile time
+        let () = IC::VALID;
+
         Sdram {
             mem: Fmc::new(fmc, rec_fmc),
-            _pins: PhantomData,
+            pins,
+            chip: SdramDescriptor::from_chip::<IC>(),
             _chip: PhantomData,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<PINS> Sdram<SdramDescriptor, PINS, Unconfigured>
+where
+    PINS: PinsSdram<stm32::FMC>,
+{
+    /// New SDRAM instance for an SDRAM described at runtime
+    ///
+    /// `pins` must be a set of pins connecting to an SDRAM on the
+    /// FMC controller, as for [`new`](#method.new). The `descriptor`
+    /// is checked when the SDRAM is initialised. It can be checked
+    /// beforehand with
+    /// [`SdramDescriptor::validate`](struct.SdramDescriptor.html#method.validate).
+    ///
+    /// If the data width of `PINS` is different to the data width of
+    /// the SDRAM, or there are not enough address lines or bank
+    /// address lines in `PINS` to access the whole SDRAM, an error is
+    /// returned together with the FMC peripheral, the FMC peripheral
+    /// record and the pins. The FMC peripheral is not modified.
+    pub fn new_dynamic(
+        fmc: stm32::FMC,
+        rec_fmc: rec::Fmc,
+        pins: PINS,
+        descriptor: &SdramDescriptor,
+    ) -> Result<Self, (stm32::FMC, rec::Fmc, PINS, SdramError)> {
+        if let Err(e) = check_pins_dynamic::<PINS>(&descriptor.config) {
+            return Err((fmc, rec_fmc, pins, e));
         }
+
+        Ok(Sdram {
+            mem: Fmc::new(fmc, rec_fmc),
+            pins,
+            chip: *descriptor,
+            _chip: PhantomData,
+            _state: PhantomData,
+        })
+    }
+}
+
+impl<IC, PINS> Sdram<IC, PINS, Unconfigured>
+where
+    PINS: PinsSdram<stm32::FMC>,
+{
+    /// Set the mapping of the FMC banks into the memory map. This
+    /// changes the base address returned by [`init`](#method.init),
+    /// and must be called before the SDRAM is initialised.
+    pub fn with_bank_mapping(mut self, bank_mapping: BankMapping) -> Self {
+        self.mem.set_bank_mapping(bank_mapping);
+        self
     }
 
     /// Initialise SDRAM instance. Delay is used to wait the SDRAM
     /// powerup delay.
     ///
-    /// Returns a raw pointer to the memory-mapped SDRAM block.
+    /// Returns the initialised SDRAM. The memory-mapped SDRAM block
+    /// can then be accessed with [`region`](#method.region).
     ///
     /// # Panics
     ///
     /// * Panics if the FMC kernel clock `fmc_ker_ck` is not running.
     ///
-    /// * Panics if any setting in `IC::CONFIG` cannot be achieved.
+    /// * Panics if any setting in the SDRAM configuration cannot be
+    /// achieved.
     ///
     /// * Panics if the FMC kernal clock `fmc_ker_ck` is too fast for
-    /// maximum SD clock in `IC::TIMING`.
+    /// maximum SD clock of the SDRAM.
+    ///
+    /// See [`try_init`](#method.try_init) for a version that returns
+    /// an error instead.
     pub fn init<D>(
-        &mut self,
+        self,
         delay: &mut D,
         core_clocks: CoreClocks,
-    ) -> *mut u32
+    ) -> Sdram<IC, PINS, Ready>
     where
         D: DelayUs<u8>,
     {
-        use SdramCommand::*;
-        use SdramTargetBank::*;
+        match self.try_init(delay, core_clocks) {
+            Ok(sdram) => sdram,
+            Err((_, e)) => panic!("Failed to initialise SDRAM: {:?}", e),
+        }
+    }
 
-        // Select bank
-        let bank = match PINS::EXTERNAL_BANK {
-            1 => Bank1,
-            2 => Bank2,
-            _ => unimplemented!(),
-        };
+    /// Initialise SDRAM instance. Delay is used to wait the SDRAM
+    /// powerup delay.
+    ///
+    /// Returns the initialised SDRAM.
+    ///
+    /// All settings are checked before any register is written. If
+    /// an error is returned, the SDRAM controller is not modified and
+    /// is returned with the error.
+    pub fn try_init<D>(
+        mut self,
+        delay: &mut D,
+        core_clocks: CoreClocks,
+    ) -> Result<Sdram<IC, PINS, Ready>, (Self, SdramError)>
+    where
+        D: DelayUs<u8>,
+    {
+        match self.configure(delay, core_clocks) {
+            Ok(()) => Ok(self.transition()),
+            Err(e) => Err((self, e)),
+        }
+    }
 
-        // Clock divider 2 ~ 3
-        assert!(
-            IC::CONFIG.sd_clock_divide >= 2 && IC::CONFIG.sd_clock_divide <= 3,
-            "SD clock divider is invalid!"
-        );
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS) {
+        let (fmc, rec_fmc) = self.mem.free();
+        (fmc, rec_fmc, self.pins)
+    }
 
-        // Calcuate SD clock from the current `fmc_ker_ck`
-        let sd_clock_hz = {
-            let fmc_ker_ck_hz = self
-                .mem
-                .get_ker_clk(core_clocks)
-                .expect("FMC kernel clock is not running!")
-                .0;
-            fmc_ker_ck_hz / IC::CONFIG.sd_clock_divide as u32
-        };
-        // Check that the SD clock is acceptable
-        assert!(
-            sd_clock_hz <= IC::TIMING.max_sd_clock_hz,
-            "FMC kernel clock is too fast for the SD
-                 clock period of the SDRAM!"
-        );
-
-        fmc_trace!(
-            "FMC clock {:?} (Max {:?})",
-            sd_clock_hz,
-            IC::TIMING.max_sd_clock_hz
-        );
+    /// Program the SDRAM controller and run the SDRAM initialisation
+    /// sequence
+    fn configure<D>(
+        &mut self,
+        delay: &mut D,
+        core_clocks: CoreClocks,
+    ) -> Result<(), SdramError>
+    where
+        D: DelayUs<u8>,
+    {
+        let fmc_ker_ck_hz = self
+            .mem
+            .get_ker_clk(core_clocks)
+            .ok_or(SdramError::KernelClockStopped)?
+            .0;
 
+        // The SDRAM is not accessed until it is initialised
         unsafe {
-            // Program device features and timing
-            self.set_features_timings(
+            init_sequence(
+                &*self.mem.fmc,
                 PINS::EXTERNAL_BANK,
-                IC::CONFIG,
-                IC::TIMING,
-            );
+                &self.chip,
+                fmc_ker_ck_hz,
+                delay,
+            )
+        }
+    }
+}
+
+impl<IC, PINS> Sdram<IC, PINS, Ready>
+where
+    PINS: PinsSdram<stm32::FMC>,
+{
+    /// The memory-mapped SDRAM block
+    ///
+    /// The region borrows the SDRAM, so the SDRAM cannot change mode
+    /// or be released while the region is in use.
+    pub fn region(&mut self) -> SdramRegion<'_> {
+        // The bank was checked when the SDRAM was initialised
+        let bank =
+            SdramTargetBank::from_pins::<PINS>().expect("Invalid SDRAM bank");
+        let ptr = bank.ptr(self.mem.bank_mapping());
+
+        unsafe { SdramRegion::new(ptr, self.chip.config.size_bytes()) }
+    }
+
+    /// Put the SDRAM into self-refresh mode. The contents of the SDRAM
+    /// are retained while the rest of the system is in a low-power
+    /// mode, but it cannot be accessed.
+    ///
+    /// The memory-mapped SDRAM block must not be accessed until
+    /// [`exit_self_refresh`](#method.exit_self_refresh) is called.
+    ///
+    /// If the SDRAM does not enter self-refresh mode, the controller
+    /// is returned with the error.
+    pub fn enter_self_refresh(
+        self,
+    ) -> Result<Sdram<IC, PINS, SelfRefresh>, (Self, SdramError)> {
+        self.change_mode(SdramCommand::Selfrefresh, SdramMode::SelfRefresh)
+    }
+
+    /// Change the part of the array that is refreshed in self-refresh
+    /// mode. The contents of the rest of the array are lost when
+    /// self-refresh mode is entered.
+    ///
+    /// Only mobile SDRAMs with an extended mode register support this,
+    /// otherwise `NoExtendedModeRegister` is returned. The other fields
+    /// of the extended mode register are unchanged.
+    pub fn set_partial_array_self_refresh(
+        &mut self,
+        partial_array_self_refresh: PartialArraySelfRefresh,
+    ) -> Result<(), SdramError> {
+        let bank = SdramTargetBank::from_pins::<PINS>()?;
 
-            // Enable controller
-            self.mem.enable();
+        unsafe {
+            load_partial_array_self_refresh(
+                &*self.mem.fmc,
+                bank,
+                &mut self.chip.extended_mode_register,
+                self.chip.config.row_bits,
+                partial_array_self_refresh,
+            )
+        }
+    }
 
-            // Step 1: Send a clock configuration enable command
-            self.send_command(ClkEnable, bank);
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins. The SDRAM is put into power-down mode, the FMC
+    /// controller is disabled and its kernel clock is stopped. The
+    /// contents of the SDRAM are lost.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS) {
+        // Ignore errors, the controller is reset below
+        if let Ok(bank) = SdramTargetBank::from_pins::<PINS>() {
+            unsafe {
+                self.mem
+                    .fmc
+                    .sdram_send_command(SdramCommand::Powerdown, bank);
+            }
+            let _ = self.mem.fmc.sdram_wait_mode(bank, SdramMode::PowerDown);
+        }
 
-            // Step 2: SDRAM powerup delay
-            let startup_delay_us = (IC::TIMING.startup_delay_ns + 999) / 1000;
-            delay.delay_us(startup_delay_us.try_into().unwrap());
+        let (fmc, rec_fmc) = self.mem.free();
+        (fmc, rec_fmc, self.pins)
+    }
 
-            // Step 3: Send a PALL (precharge all) command
-            self.send_command(Pall, bank);
+    /// Put the SDRAM into power-down mode.
+    ///
+    /// The memory-mapped SDRAM block must not be accessed until
+    /// [`exit_power_down`](#method.exit_power_down) is called.
+    ///
+    /// If the SDRAM does not enter power-down mode, the controller is
+    /// returned with the error.
+    pub fn enter_power_down(
+        self,
+    ) -> Result<Sdram<IC, PINS, PowerDown>, (Self, SdramError)> {
+        self.change_mode(SdramCommand::Powerdown, SdramMode::PowerDown)
+    }
+}
 
-            // Step 4: Send eight auto refresh commands
-            self.send_command(Autorefresh(8), bank);
+impl<IC, PINS> Sdram<IC, PINS, SelfRefresh>
+where
+    PINS: PinsSdram<stm32::FMC>,
+{
+    /// Return the SDRAM to normal mode from self-refresh mode.
+    ///
+    /// The controller waits for the exit self-refresh delay (tXSR)
+    /// programmed in the SDTR register before issuing the next
+    /// command.
+    ///
+    /// If the SDRAM does not return to normal mode, the controller is
+    /// returned with the error.
+    pub fn exit_self_refresh(
+        self,
+    ) -> Result<Sdram<IC, PINS, Ready>, (Self, SdramError)> {
+        self.change_mode(SdramCommand::NormalMode, SdramMode::Normal)
+    }
 
-            // Step 5: Program the SDRAM's mode register
-            self.send_command(LoadMode(IC::MODE_REGISTER), bank);
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins. The FMC controller is disabled and its kernel clock is
+    /// stopped. The SDRAM is no longer refreshed, so its contents are
+    /// lost.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS) {
+        let (fmc, rec_fmc) = self.mem.free();
+        (fmc, rec_fmc, self.pins)
+    }
 
-            // Step 6: Set the refresh rate counter
-            // period (ns) * frequency (hz) / 10^9 = count
-            let refresh_counter_top = ((IC::TIMING.refresh_period_ns as u64
-                * sd_clock_hz as u64)
-                / 1_000_000_000)
-                - 20;
-            assert!(
-                refresh_counter_top >= 41 && refresh_counter_top < (1 << 13),
-                "Impossible configuration for H7 FMC Controller"
-            );
-            self.mem
-                .fmc
-                .sdrtr
-                .modify(|_, w| w.count().bits(refresh_counter_top as u16));
+    /// Reprogram the SDRAM timings and refresh rate for a new FMC
+    /// kernel clock `fmc_ker_ck`.
+    ///
+    /// To change the kernel clock whilst retaining the contents of the
+    /// SDRAM:
+    ///
+    /// 1. Put the SDRAM into self-refresh mode with
+    /// [`enter_self_refresh`](#method.enter_self_refresh)
+    /// 2. Change the kernel clock
+    /// 3. Call this method with the new clocks
+    /// 4. Return the SDRAM to normal mode with
+    /// [`exit_self_refresh`](#method.exit_self_refresh)
+    ///
+    /// The SD clock divider cannot be changed without re-initialising
+    /// the SDRAM, so the divider selected at initialisation is kept.
+    ///
+    /// If the new SD clock is too fast for the SDRAM, or the timings
+    /// cannot be achieved, an error is returned and no registers are
+    /// modified. The SDRAM remains in self-refresh mode.
+    pub fn reclock(
+        &mut self,
+        new_clocks: CoreClocks,
+    ) -> Result<(), SdramError> {
+        let bank = SdramTargetBank::from_pins::<PINS>()?;
+        let fmc_ker_ck_hz = self
+            .mem
+            .get_ker_clk(new_clocks)
+            .ok_or(SdramError::KernelClockStopped)?
+            .0;
+
+        // The SDRAM is in self-refresh mode
+        unsafe {
+            reclock_sequence(&*self.mem.fmc, bank, &self.chip, fmc_ker_ck_hz)
+        }
+    }
+}
+
+impl<IC, PINS> Sdram<IC, PINS, PowerDown>
+where
+    PINS: PinsSdram<stm32::FMC>,
+{
+    /// Return the SDRAM to normal mode from power-down mode.
+    ///
+    /// If the SDRAM does not return to normal mode, the controller is
+    /// returned with the error.
+    pub fn exit_power_down(
+        self,
+    ) -> Result<Sdram<IC, PINS, Ready>, (Self, SdramError)> {
+        self.change_mode(SdramCommand::NormalMode, SdramMode::Normal)
+    }
+
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins. The FMC controller is disabled and its kernel clock is
+    /// stopped. The contents of the SDRAM are lost.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS) {
+        let (fmc, rec_fmc) = self.mem.free();
+        (fmc, rec_fmc, self.pins)
+    }
+}
+
+impl<IC, PINS, STATE> Sdram<IC, PINS, STATE> {
+    /// Enable the refresh error interrupt. The FMC interrupt is
+    /// raised when a refresh error is detected. See
+    /// [`on_refresh_error_interrupt`](fn.on_refresh_error_interrupt.html)
+    pub fn listen_refresh_error(&mut self) {
+        self.mem.fmc.sdram_listen_refresh_error(true);
+    }
+
+    /// Disable the refresh error interrupt
+    pub fn unlisten_refresh_error(&mut self) {
+        self.mem.fmc.sdram_listen_refresh_error(false);
+    }
+
+    /// Returns `true` if a refresh error has been detected. A refresh
+    /// error occurs when a refresh request could not be serviced
+    /// before the next refresh request, for example because the bus
+    /// is heavily loaded
+    pub fn is_refresh_error(&self) -> bool {
+        self.mem.fmc.sdram_is_refresh_error()
+    }
+
+    /// If a refresh error has been detected, clear it and increment
+    /// the refresh error count. Returns `true` if there was a
+    /// refresh error
+    ///
+    /// Interrupts are disabled while the error is cleared, so that an
+    /// error is not also counted by
+    /// [`on_refresh_error_interrupt`](fn.on_refresh_error_interrupt.html)
+    pub fn clear_refresh_error(&mut self) -> bool {
+        interrupt::free(|_| self.mem.fmc.sdram_clear_refresh_error())
+    }
+
+    /// Number of refresh errors that have been cleared, either by
+    /// [`clear_refresh_error`](#method.clear_refresh_error) or
+    /// [`on_refresh_error_interrupt`](fn.on_refresh_error_interrupt.html)
+    pub fn refresh_error_count(&self) -> u32 {
+        REFRESH_ERRORS.load(Ordering::Relaxed)
+    }
+
+    /// Send a command that changes the SDRAM mode, and wait for the
+    /// SDRAM to enter `mode`. On success the type state becomes `NEW`,
+    /// otherwise the controller is returned with the error
+    fn change_mode<NEW>(
+        self,
+        command: SdramCommand,
+        mode: SdramMode,
+    ) -> Result<Sdram<IC, PINS, NEW>, (Self, SdramError)>
+    where
+        PINS: PinsSdram<stm32::FMC>,
+    {
+        let result =
+            SdramTargetBank::from_pins::<PINS>().and_then(|bank| unsafe {
+                self.mem.fmc.sdram_change_mode(bank, command, mode)
+            });
+
+        match result {
+            Ok(()) => Ok(self.transition()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Change the type state of the controller
+    fn transition<NEW>(self) -> Sdram<IC, PINS, NEW> {
+        Sdram {
+            mem: self.mem,
+            pins: self.pins,
+            chip: self.chip,
+            _chip: PhantomData,
+            _state: PhantomData,
+        }
+    }
+}
+
+/// Program the SDRAM controller and run the SDRAM initialisation
+/// sequence, for the SDRAM on SDRAM bank `external_bank` (1 or 2) and
+/// an FMC kernel clock `fmc_ker_ck_hz`
+///
+/// This is the part of
+/// [`Sdram::try_init`](struct.Sdram.html#method.try_init) that
+/// accesses the FMC registers. It can be run on the host with a
+/// `FakeFmc` (`std` feature).
+///
+/// All settings are checked before any register is written.
+///
+/// # Safety
+///
+/// The SDRAM must not be accessed while it is being initialised.
+pub unsafe fn init_sequence<R, D>(
+    regs: &R,
+    external_bank: u8,
+    chip: &SdramDescriptor,
+    fmc_ker_ck_hz: u32,
+    delay: &mut D,
+) -> Result<(), SdramError>
+where
+    R: FmcRegisters + ?Sized,
+    D: DelayUs<u8>,
+{
+    // Select bank
+    let bank = SdramTargetBank::from_external_bank(external_bank)?;
+
+    // Check device features and timing
+    chip.validate()?;
+    let extended_mode = match chip.extended_mode_register {
+        Some(emr) => Some(emr.load_value(chip.config.row_bits)?),
+        None => None,
+    };
+
+    // Calcuate SD clock from `fmc_ker_ck`
+    let (sd_clock_divide, sd_clock_hz) = chip
+        .config
+        .sd_clock_divide
+        .select(fmc_ker_ck_hz, chip.timing.max_sd_clock_hz)?;
+
+    fmc_trace!(
+        "FMC clock {:?} (Max {:?})",
+        sd_clock_hz,
+        chip.timing.max_sd_clock_hz
+    );
+
+    // Round up timings to SD clock cycles
+    let cycles = chip.timing.cycles(sd_clock_hz)?;
+    // Refresh rate counter
+    let refresh_counter_top = chip.timing.refresh_counter(sd_clock_hz)?;
+
+    // Program device features and timing
+    regs.sdram_set_common(&chip.config, sd_clock_divide, &cycles);
+    regs.sdram_set_features_timings(bank, &chip.config, &cycles);
+
+    initialisation_commands(
+        regs,
+        bank,
+        chip.timing.startup_delay_us(),
+        &[
+            Some((bank, chip.mode_register.bits())),
+            extended_mode.map(|extended_mode| (bank, extended_mode)),
+        ],
+        refresh_counter_top,
+        delay,
+    );
+
+    // Memory now initialised
+    Ok(())
+}
+
+/// Program the SDRAM controller and run the SDRAM initialisation
+/// sequence, for SDRAMs on both SDRAM banks and an FMC kernel clock
+/// `fmc_ker_ck_hz`
+///
+/// This is the part of
+/// [`SdramDual::try_init`](struct.SdramDual.html#method.try_init) that
+/// accesses the FMC registers. See
+/// [`init_sequence`](fn.init_sequence.html).
+///
+/// All settings are checked before any register is written.
+///
+/// # Safety
+///
+/// The SDRAMs must not be accessed while they are being initialised.
+pub unsafe fn init_sequence_dual<R, D>(
+    regs: &R,
+    chip1: &SdramDescriptor,
+    chip2: &SdramDescriptor,
+    fmc_ker_ck_hz: u32,
+    delay: &mut D,
+) -> Result<(), SdramError>
+where
+    R: FmcRegisters + ?Sized,
+    D: DelayUs<u8>,
+{
+    use SdClockDivide::*;
+    use SdramTargetBank::*;
+
+    // Check device features and timing
+    chip1.validate()?;
+    chip2.validate()?;
+    let extended_mode1 = match chip1.extended_mode_register {
+        Some(emr) => Some(emr.load_value(chip1.config.row_bits)?),
+        None => None,
+    };
+    let extended_mode2 = match chip2.extended_mode_register {
+        Some(emr) => Some(emr.load_value(chip2.config.row_bits)?),
+        None => None,
+    };
+
+    // The SD clock is common to both banks. Select a divider that
+    // meets both SDRAMs
+    let divide =
+        match (chip1.config.sd_clock_divide, chip2.config.sd_clock_divide) {
+            (Auto, divide) | (divide, Auto) => divide,
+            (divide1, divide2) if divide1 == divide2 => divide1,
+            _ => return Err(SdramError::IncompatibleClockDivide),
+        };
+    let max_sd_clock_hz =
+        cmp::min(chip1.timing.max_sd_clock_hz, chip2.timing.max_sd_clock_hz);
+
+    // Calcuate SD clock from `fmc_ker_ck`
+    let (sd_clock_divide, sd_clock_hz) =
+        divide.select(fmc_ker_ck_hz, max_sd_clock_hz)?;
+
+    fmc_trace!("FMC clock {:?} (Max {:?})", sd_clock_hz, max_sd_clock_hz);
+
+    // Round up timings to SD clock cycles, using the stricter row
+    // cycle, row precharge and write recovery delays for both banks
+    let (cycles1, cycles2) = SdramCycles::common(
+        chip1.timing.cycles(sd_clock_hz)?,
+        chip2.timing.cycles(sd_clock_hz)?,
+    )?;
+
+    // Common features: the longer read pipe delay, and read burst only
+    // if both SDRAMs allow it
+    let common = FmcSdramConfiguration {
+        read_pipe_delay_cycles: cmp::max(
+            chip1.config.read_pipe_delay_cycles,
+            chip2.config.read_pipe_delay_cycles,
+        ),
+        read_burst: chip1.config.read_burst && chip2.config.read_burst,
+        ..chip1.config
+    };
+
+    // Refresh rate counter, from the SDRAM that needs refreshing most
+    // often
+    let refresh_counter_top = cmp::min(
+        chip1.timing.refresh_counter(sd_clock_hz)?,
+        chip2.timing.refresh_counter(sd_clock_hz)?,
+    );
+
+    // Program device features and timing
+    regs.sdram_set_common(&common, sd_clock_divide, &cycles1);
+    regs.sdram_set_features_timings(Bank1, &chip1.config, &cycles1);
+    regs.sdram_set_features_timings(Bank2, &chip2.config, &cycles2);
+
+    // Load the mode registers together if they are the same
+    let (mode1, mode2) =
+        (chip1.mode_register.bits(), chip2.mode_register.bits());
+    let (mode1, mode2) = if mode1 == mode2 {
+        (Some((Both, mode1)), None)
+    } else {
+        (Some((Bank1, mode1)), Some((Bank2, mode2)))
+    };
+
+    initialisation_commands(
+        regs,
+        Both,
+        cmp::max(
+            chip1.timing.startup_delay_us(),
+            chip2.timing.startup_delay_us(),
+        ),
+        &[
+            mode1,
+            mode2,
+            extended_mode1.map(|extended_mode| (Bank1, extended_mode)),
+            extended_mode2.map(|extended_mode| (Bank2, extended_mode)),
+        ],
+        refresh_counter_top,
+        delay,
+    );
+
+    // Memory now initialised
+    Ok(())
+}
+
+/// Enable the controller and send the commands of the SDRAM
+/// initialisation sequence to the SDRAMs on `bank`. `load_mode` are the
+/// values for each LOAD MODE REGISTER command, and the banks they are
+/// sent to
+///
+/// # Safety
+///
+/// The SDRAM controller must have been programmed with settings that
+/// have been checked.
+unsafe fn initialisation_commands<R, D>(
+    regs: &R,
+    bank: SdramTargetBank,
+    startup_delay_us: u32,
+    load_mode: &[Option<(SdramTargetBank, u16)>],
+    refresh_counter_top: u16,
+    delay: &mut D,
+) where
+    R: FmcRegisters + ?Sized,
+    D: DelayUs<u8>,
+{
+    use SdramCommand::*;
+
+    // Enable controller
+    regs.fmc_enable();
+
+    // Step 1: Send a clock configuration enable command
+    regs.sdram_send_command(ClkEnable, bank);
+
+    // Step 2: SDRAM powerup delay
+    delay.delay_us(startup_delay_us as u8);
+
+    // Step 3: Send a PALL (precharge all) command
+    regs.sdram_send_command(Pall, bank);
+
+    // Step 4: Send eight auto refresh commands
+    regs.sdram_send_command(Autorefresh(8), bank);
+
+    // Step 5: Program the SDRAMs' mode registers
+    for &(bank, mode) in load_mode.iter().flatten() {
+        regs.sdram_send_command(LoadMode(mode), bank);
+    }
+
+    // Step 6: Set the refresh rate counter
+    regs.sdram_set_refresh_counter(refresh_counter_top);
+}
+
+/// Reprogram the timings and refresh rate of the SDRAM `chip` on
+/// `bank` for a new FMC kernel clock `fmc_ker_ck_hz`. The SD clock
+/// divider is unchanged
+///
+/// All settings are checked before any register is written.
+///
+/// # Safety
+///
+/// The SDRAM must be in self-refresh mode.
+unsafe fn reclock_sequence<R>(
+    regs: &R,
+    bank: SdramTargetBank,
+    chip: &SdramDescriptor,
+    fmc_ker_ck_hz: u32,
+) -> Result<(), SdramError>
+where
+    R: FmcRegisters + ?Sized,
+{
+    // Calcuate SD clock from the new `fmc_ker_ck`
+    let (_, sd_clock_hz) = regs
+        .sdram_clock_divide()
+        .select(fmc_ker_ck_hz, chip.timing.max_sd_clock_hz)?;
+
+    fmc_trace!(
+        "FMC clock {:?} (Max {:?})",
+        sd_clock_hz,
+        chip.timing.max_sd_clock_hz
+    );
+
+    // Round up timings to SD clock cycles
+    let cycles = chip.timing.cycles(sd_clock_hz)?;
+    // Refresh rate counter
+    let refresh_counter_top = chip.timing.refresh_counter(sd_clock_hz)?;
+
+    regs.sdram_set_common_timings(&cycles);
+    regs.sdram_set_features_timings(bank, &chip.config, &cycles);
+    regs.sdram_set_refresh_counter(refresh_counter_top);
+
+    Ok(())
+}
+
+/// Load the extended mode register of the SDRAM on `bank` with a new
+/// partial array self-refresh, and store it in `extended_mode_register`
+///
+/// # Safety
+///
+/// The SDRAM must not be accessed while the mode register is loaded.
+unsafe fn load_partial_array_self_refresh<R>(
+    regs: &R,
+    bank: SdramTargetBank,
+    extended_mode_register: &mut Option<ExtendedModeRegister>,
+    row_bits: u8,
+    partial_array_self_refresh: PartialArraySelfRefresh,
+) -> Result<(), SdramError>
+where
+    R: FmcRegisters + ?Sized,
+{
+    use SdramCommand::*;
+
+    let emr = extended_mode_register
+        .ok_or(SdramError::NoExtendedModeRegister)?
+        .partial_array_self_refresh(partial_array_self_refresh);
+    let extended_mode = emr.load_value(row_bits)?;
+
+    // All banks must be idle for LOAD MODE REGISTER
+    regs.sdram_send_command(Pall, bank);
+    regs.sdram_send_command(LoadMode(extended_mode), bank);
+
+    *extended_mode_register = Some(emr);
+    Ok(())
+}
+
+/// Handle a refresh error interrupt. This should be called from the
+/// FMC interrupt handler.
+///
+/// If a refresh error has been detected, it is cleared and the
+/// refresh error count is incremented. Returns `true` if there was a
+/// refresh error.
+pub fn on_refresh_error_interrupt() -> bool {
+    // Reading SDSR has no side effects, and writing CRE only clears
+    // the refresh error flag
+    let fmc = unsafe { &*stm32::FMC::ptr() };
+
+    fmc.sdram_clear_refresh_error()
+}
+
+impl<IC1, PINS1, IC2, PINS2, STATE> SdramDual<IC1, PINS1, IC2, PINS2, STATE> {
+    /// Enable the refresh error interrupt. See
+    /// [`Sdram::listen_refresh_error`](struct.Sdram.html#method.listen_refresh_error)
+    pub fn listen_refresh_error(&mut self) {
+        self.mem.fmc.sdram_listen_refresh_error(true);
+    }
+
+    /// Disable the refresh error interrupt
+    pub fn unlisten_refresh_error(&mut self) {
+        self.mem.fmc.sdram_listen_refresh_error(false);
+    }
+
+    /// Returns `true` if a refresh error has been detected on either
+    /// SDRAM bank
+    pub fn is_refresh_error(&self) -> bool {
+        self.mem.fmc.sdram_is_refresh_error()
+    }
+
+    /// If a refresh error has been detected, clear it and increment
+    /// the refresh error count. Returns `true` if there was a
+    /// refresh error
+    ///
+    /// Interrupts are disabled while the error is cleared, so that an
+    /// error is not also counted by
+    /// [`on_refresh_error_interrupt`](fn.on_refresh_error_interrupt.html)
+    pub fn clear_refresh_error(&mut self) -> bool {
+        interrupt::free(|_| self.mem.fmc.sdram_clear_refresh_error())
+    }
+
+    /// Number of refresh errors that have been cleared
+    pub fn refresh_error_count(&self) -> u32 {
+        REFRESH_ERRORS.load(Ordering::Relaxed)
+    }
+
+    /// Change the type state of the controller
+    fn transition<NEW>(self) -> SdramDual<IC1, PINS1, IC2, PINS2, NEW> {
+        SdramDual {
+            mem: self.mem,
+            pins: self.pins,
+            extended_mode_registers: self.extended_mode_registers,
+            _chip: PhantomData,
+            _state: PhantomData,
         }
+    }
+}
 
-        // Memory now initialised. Return base address
-        match PINS::EXTERNAL_BANK {
-            1 => FmcBank::Bank5.ptr(),
-            2 => FmcBank::Bank6.ptr(),
-            _ => unimplemented!(),
+impl<IC1, PINS1, IC2, PINS2> SdramDual<IC1, PINS1, IC2, PINS2, Unconfigured>
+where
+    IC1: SdramChip,
+    IC2: SdramChip,
+    PINS1: PinsSdram<stm32::FMC>,
+    PINS2: PinsSdram<stm32::FMC>,
+{
+    /// New instance for two SDRAMs, one on each SDRAM bank
+    ///
+    /// `pins1` must be a set of pins connecting to an SDRAM on
+    /// SDRAM bank 1, and `pins2` a set of pins connecting to an
+    /// SDRAM on SDRAM bank 2. Pins that are common between the banks,
+    /// such as the address and data lines, can be reconstructed with
+    /// `unsafe` code.
+    ///
+    /// Fails to compile if the data width of either set of pins is
+    /// different to the data width of its SDRAM, or if either set of
+    /// pins is on the wrong SDRAM bank.
+    ///
+    /// # Panics
+    ///
+    /// * Panics if there are not enough address lines in the pins to
+    /// access the whole of either SDRAM.
+    ///
+    /// * Panics if there are not enough bank address lines in the
+    /// pins to access the whole of either SDRAM.
+    pub fn new(
+        fmc: stm32::FMC,
+        rec_fmc: rec::Fmc,
+        pins1: PINS1,
+        _chip1: IC1,
+        pins2: PINS2,
+        _chip2: IC2,
+    ) -> Self {
+        // Check the chip definitions and pin banks at compile time
+        let () = IC1::VALID;
+        let () = IC2::VALID;
+        let () = PinsCheck::<IC1, PINS1>::BANK1;
+        let () = PinsCheck::<IC2, PINS2>::BANK2;
+
+        check_pins::<IC1, PINS1>();
+        check_pins::<IC2, PINS2>();
+
+        SdramDual {
+            mem: Fmc::new(fmc, rec_fmc),
+            pins: (pins1, pins2),
+            extended_mode_registers: (
+                IC1::EXTENDED_MODE_REGISTER,
+                IC2::EXTENDED_MODE_REGISTER,
+            ),
+            _chip: PhantomData,
+            _state: PhantomData,
         }
     }
+}
 
-    /// Program memory device features and timings.
+impl<IC1, PINS1, IC2, PINS2> SdramDual<IC1, PINS1, IC2, PINS2, Unconfigured>
+where
+    IC1: SdramChip,
+    IC2: SdramChip,
+{
+    /// Set the mapping of the FMC banks into the memory map. See
+    /// [`Sdram::with_bank_mapping`](struct.Sdram.html#method.with_bank_mapping)
+    pub fn with_bank_mapping(mut self, bank_mapping: BankMapping) -> Self {
+        self.mem.set_bank_mapping(bank_mapping);
+        self
+    }
+
+    /// Initialise both SDRAMs. Delay is used to wait the SDRAM
+    /// powerup delay.
     ///
-    /// # Safety
+    /// Returns the initialised SDRAMs. The memory-mapped SDRAM blocks
+    /// can then be accessed with [`regions`](#method.regions).
     ///
-    /// Some settings are common between both
-    /// banks. Calling this function mutliple times with different
-    /// banks and different configurations is unsafe. Refer to
-    /// RM0433 Section 21.9 / RM0399 Section 23.9.
-    unsafe fn set_features_timings(
+    /// # Panics
+    ///
+    /// Panics if [`try_init`](#method.try_init) returns an error.
+    pub fn init<D>(
+        self,
+        delay: &mut D,
+        core_clocks: CoreClocks,
+    ) -> SdramDual<IC1, PINS1, IC2, PINS2>
+    where
+        D: DelayUs<u8>,
+    {
+        match self.try_init(delay, core_clocks) {
+            Ok(sdram) => sdram,
+            Err((_, e)) => panic!("Failed to initialise SDRAM: {:?}", e),
+        }
+    }
+
+    /// Initialise both SDRAMs. Delay is used to wait the SDRAM
+    /// powerup delay.
+    ///
+    /// Returns the initialised SDRAMs.
+    ///
+    /// All settings are checked before any register is written. If
+    /// an error is returned, the SDRAM controller is not modified and
+    /// is returned with the error.
+    pub fn try_init<D>(
+        mut self,
+        delay: &mut D,
+        core_clocks: CoreClocks,
+    ) -> Result<SdramDual<IC1, PINS1, IC2, PINS2>, (Self, SdramError)>
+    where
+        D: DelayUs<u8>,
+    {
+        match self.configure(delay, core_clocks) {
+            Ok(()) => Ok(self.transition()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS1, PINS2) {
+        let (fmc, rec_fmc) = self.mem.free();
+        let (pins1, pins2) = self.pins;
+        (fmc, rec_fmc, pins1, pins2)
+    }
+
+    /// Program the SDRAM controller and run the SDRAM initialisation
+    /// sequence for both SDRAMs
+    fn configure<D>(
         &mut self,
-        sdram_bank: u8,
-        config: FmcSdramConfiguration,
-        timing: FmcSdramTiming,
-    ) {
-        // SDRAM Controller/Timing registers
-        let sd = match sdram_bank {
-            1 => self.mem.fmc.sdbank1(),
-            2 => self.mem.fmc.sdbank2(),
-            _ => panic!(),
+        delay: &mut D,
+        core_clocks: CoreClocks,
+    ) -> Result<(), SdramError>
+    where
+        D: DelayUs<u8>,
+    {
+        let fmc_ker_ck_hz = self
+            .mem
+            .get_ker_clk(core_clocks)
+            .ok_or(SdramError::KernelClockStopped)?
+            .0;
+
+        // The SDRAMs are not accessed until they are initialised
+        unsafe {
+            init_sequence_dual(
+                &*self.mem.fmc,
+                &SdramDescriptor::from_chip::<IC1>(),
+                &SdramDescriptor::from_chip::<IC2>(),
+                fmc_ker_ck_hz,
+                delay,
+            )
+        }
+    }
+}
+
+impl<IC1, PINS1, IC2, PINS2> SdramDual<IC1, PINS1, IC2, PINS2, Ready>
+where
+    IC1: SdramChip,
+    IC2: SdramChip,
+{
+    /// The memory-mapped SDRAM blocks on bank 1 and bank 2
+    /// respectively
+    ///
+    /// The regions borrow the SDRAMs, so the SDRAMs cannot be released
+    /// while the regions are in use.
+    pub fn regions(&mut self) -> (SdramRegion<'_>, SdramRegion<'_>) {
+        use SdramTargetBank::*;
+
+        let bank_mapping = self.mem.bank_mapping();
+        unsafe {
+            (
+                SdramRegion::new(
+                    Bank1.ptr(bank_mapping),
+                    IC1::CONFIG.size_bytes(),
+                ),
+                SdramRegion::new(
+                    Bank2.ptr(bank_mapping),
+                    IC2::CONFIG.size_bytes(),
+                ),
+            )
+        }
+    }
+
+    /// Change the part of the array that is refreshed in self-refresh
+    /// mode, for the SDRAM on SDRAM bank `external_bank` (1 or 2). See
+    /// [`Sdram::set_partial_array_self_refresh`](struct.Sdram.html#method.set_partial_array_self_refresh)
+    pub fn set_partial_array_self_refresh(
+        &mut self,
+        external_bank: u8,
+        partial_array_self_refresh: PartialArraySelfRefresh,
+    ) -> Result<(), SdramError> {
+        let bank = SdramTargetBank::from_external_bank(external_bank)?;
+        let (extended_mode_register, row_bits) = match bank {
+            SdramTargetBank::Bank1 => {
+                (&mut self.extended_mode_registers.0, IC1::CONFIG.row_bits)
+            }
+            _ => (&mut self.extended_mode_registers.1, IC2::CONFIG.row_bits),
         };
 
-        // Features ---- SDCR REGISTER
+        unsafe {
+            load_partial_array_self_refresh(
+                &*self.mem.fmc,
+                bank,
+                extended_mode_register,
+                row_bits,
+                partial_array_self_refresh,
+            )
+        }
+    }
 
-        // CAS latency 1 ~ 3 cycles
-        assert!(
-            config.cas_latency >= 1 && config.cas_latency <= 3,
-            "Impossible configuration for H7 FMC Controller"
-        );
+    /// Release the FMC peripheral, the FMC peripheral record and the
+    /// pins. Both SDRAMs are put into power-down mode, the FMC
+    /// controller is disabled and its kernel clock is stopped. The
+    /// contents of the SDRAMs are lost.
+    pub fn free(self) -> (stm32::FMC, rec::Fmc, PINS1, PINS2) {
+        use SdramTargetBank::*;
 
-        // Row Bits: 11 ~ 13
-        assert!(
-            config.row_bits >= 11 && config.row_bits <= 13,
-            "Impossible configuration for H7 FMC Controller"
-        );
+        // Ignore errors, the controller is reset below
+        unsafe {
+            self.mem
+                .fmc
+                .sdram_send_command(SdramCommand::Powerdown, Both);
+        }
+        let _ = self.mem.fmc.sdram_wait_mode(Bank1, SdramMode::PowerDown);
+        let _ = self.mem.fmc.sdram_wait_mode(Bank2, SdramMode::PowerDown);
 
-        // Column bits: 8 ~ 11
-        assert!(
-            config.column_bits >= 8 && config.column_bits <= 11,
-            "Impossible configuration for H7 FMC Controller"
-        );
+        let (fmc, rec_fmc) = self.mem.free();
+        let (pins1, pins2) = self.pins;
+        (fmc, rec_fmc, pins1, pins2)
+    }
+}
 
-        // Read Pipe Delay Cycles 0 ~ 2
-        assert!(
-            config.read_pipe_delay_cycles <= 2,
-            "Impossible configuration for H7 FMC Controller"
-        );
+/// SDRAM controller registers
+///
+/// Implemented for all [`FmcRegisters`](trait.FmcRegisters.html)
+trait SdramRegisters: FmcRegisters {
+    /// Enable FMC controller
+    fn fmc_enable(&self) {
+        // The FMCEN bit of the FMC_BCR2..4 registers is don’t
+        // care. It is only enabled through the FMC_BCR1 register.
+        unsafe {
+            self.modify(FmcRegister::Bcr1, |r| bcr1::FMCEN.set(r, 1));
+        }
+    }
 
-        // Common settings written to SDCR1 only
-        self.mem.fmc.sdbank1().sdcr.modify(|_, w| {
-            w.rpipe()
-                .bits(config.read_pipe_delay_cycles)
-                .rburst()
-                .bit(config.read_burst)
-                .sdclk()
-                .bits(config.sd_clock_divide)
+    /// Program settings that are common between both SDRAM banks.
+    ///
+    /// These are written to SDCR1 and SDTR1 only. Refer to RM0433
+    /// Section 21.9 / RM0399 Section 23.9.
+    ///
+    /// # Safety
+    ///
+    /// `config` must have been checked with
+    /// [`validate`](fn.validate.html).
+    unsafe fn sdram_set_common(
+        &self,
+        config: &FmcSdramConfiguration,
+        sd_clock_divide: u8,
+        timing: &SdramCycles,
+    ) {
+        self.modify(FmcRegister::Sdcr1, |r| {
+            let r = sdcr::RPIPE.set(r, config.read_pipe_delay_cycles.into());
+            let r = sdcr::RBURST.set(r, config.read_burst.into());
+            sdcr::SDCLK.set(r, sd_clock_divide.into())
         });
-        sd.sdcr.modify(|_, w| {
-            w.wp()
-                .bit(config.write_protection)
-                .cas()
-                .bits(config.cas_latency)
-                .nb()
-                .bit(match config.internal_banks {
-                    2 => false,
-                    4 => true,
-                    _ => {
-                        panic!("Impossible configuration for H7 FMC Controller")
-                    }
-                })
-                .mwid()
-                .bits(match config.memory_data_width {
+        self.sdram_set_common_timings(timing);
+    }
+
+    /// Program timings that are common between both SDRAM banks.
+    unsafe fn sdram_set_common_timings(&self, timing: &SdramCycles) {
+        self.modify(FmcRegister::Sdtr1, |r| {
+            let r = sdtr::TRC.set(r, (timing.row_cycle - 1).into());
+            sdtr::TRP.set(r, (timing.row_precharge - 1).into())
+        });
+    }
+
+    /// Current SD clock divider
+    fn sdram_clock_divide(&self) -> SdClockDivide {
+        match sdcr::SDCLK.get(self.read(FmcRegister::Sdcr1)) {
+            2 => SdClockDivide::Div2,
+            _ => SdClockDivide::Div3,
+        }
+    }
+
+    /// Program memory device features and timings for one SDRAM bank.
+    ///
+    /// # Safety
+    ///
+    /// `config` must have been checked with
+    /// [`validate`](fn.validate.html).
+    unsafe fn sdram_set_features_timings(
+        &self,
+        sdram_bank: SdramTargetBank,
+        config: &FmcSdramConfiguration,
+        timing: &SdramCycles,
+    ) {
+        // SDRAM Controller/Timing registers
+        let (sdcr_reg, sdtr_reg) = match sdram_bank {
+            SdramTargetBank::Bank1 => (FmcRegister::Sdcr1, FmcRegister::Sdtr1),
+            _ => (FmcRegister::Sdcr2, FmcRegister::Sdtr2),
+        };
+
+        // Features ---- SDCR REGISTER
+        self.modify(sdcr_reg, |r| {
+            let r = sdcr::WP.set(r, config.write_protection.into());
+            let r = sdcr::CAS.set(r, config.cas_latency.into());
+            let r = sdcr::NB.set(r, (config.internal_banks == 4).into());
+            let r = sdcr::MWID.set(
+                r,
+                match config.memory_data_width {
                     8 => 0,
                     16 => 1,
-                    32 => 2,
-                    _ => {
-                        panic!("Impossible configuration for H7 FMC Controller")
-                    }
-                })
-                .nr()
-                .bits(config.row_bits - 11)
-                .nc()
-                .bits(config.column_bits - 8)
+                    _ => 2,
+                },
+            );
+            let r = sdcr::NR.set(r, (config.row_bits - 11).into());
+            sdcr::NC.set(r, (config.column_bits - 8).into())
         });
 
         // Timing ---- SDTR REGISTER
@@ -363,39 +2209,95 @@ where
         // Self refresh >= ACTIVE to PRECHARGE
         let minimum_self_refresh = timing.active_to_precharge;
 
-        // Write recovery - Self refresh
-        let write_recovery_self_refresh =
-            minimum_self_refresh - timing.row_to_column;
-        // Write recovery - WRITE command to PRECHARGE command
-        let write_recovery_row_cycle =
-            timing.row_cycle - timing.row_to_column - timing.row_precharge;
-        let write_recovery =
-            cmp::max(write_recovery_self_refresh, write_recovery_row_cycle);
-
-        // Common seting written to SDTR1 only
-        self.mem.fmc.sdbank1().sdtr.modify(|_, w| {
-            w.trc()
-                .bits(timing.row_cycle - 1)
-                .trp()
-                .bits(timing.row_precharge - 1)
+        self.modify(sdtr_reg, |r| {
+            let r = sdtr::TRCD.set(r, (timing.row_to_column - 1).into());
+            let r = sdtr::TWR.set(r, (timing.write_recovery - 1).into());
+            let r = sdtr::TRAS.set(r, (minimum_self_refresh - 1).into());
+            let r = sdtr::TXSR.set(r, (timing.exit_self_refresh - 1).into());
+            sdtr::TMRD.set(r, (timing.mode_register_to_active - 1).into())
         });
-        sd.sdtr.modify(|_, w| {
-            w.trcd()
-                .bits(timing.row_to_column - 1)
-                .twr()
-                .bits(write_recovery - 1)
-                .tras()
-                .bits(minimum_self_refresh - 1)
-                .txsr()
-                .bits(timing.exit_self_refresh - 1)
-                .tmrd()
-                .bits(timing.mode_register_to_active - 1)
+    }
+
+    /// Enable or disable the refresh error interrupt
+    fn sdram_listen_refresh_error(&self, enable: bool) {
+        unsafe {
+            self.modify(FmcRegister::Sdrtr, |r| {
+                sdrtr::REIE.set(r, enable.into())
+            });
+        }
+    }
+
+    /// Returns `true` if the refresh error flag is set
+    fn sdram_is_refresh_error(&self) -> bool {
+        sdsr::RE.get(self.read(FmcRegister::Sdsr)) != 0
+    }
+
+    /// If a refresh error has been detected, clear it and increment
+    /// the refresh error count
+    fn sdram_clear_refresh_error(&self) -> bool {
+        if self.sdram_is_refresh_error() {
+            // Write 1 to CRE to clear the refresh error flag
+            unsafe {
+                self.modify(FmcRegister::Sdrtr, |r| sdrtr::CRE.set(r, 1));
+            }
+            let _ = REFRESH_ERRORS.fetch_add(1, Ordering::Relaxed);
+
+            fmc_trace!("SDRAM refresh error");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Wait for an SDRAM bank to report `mode` in the SDSR register
+    fn sdram_wait_mode(
+        &self,
+        sdram_bank: SdramTargetBank,
+        mode: SdramMode,
+    ) -> Result<(), SdramError> {
+        for _ in 0..MODE_CHANGE_TIMEOUT {
+            let sdsr = self.read(FmcRegister::Sdsr);
+            let modes = match sdram_bank {
+                SdramTargetBank::Bank1 => sdsr::MODES1.get(sdsr),
+                _ => sdsr::MODES2.get(sdsr),
+            };
+            if modes == mode as u32 {
+                return Ok(());
+            }
+        }
+
+        Err(SdramError::ModeChangeTimeout)
+    }
+
+    /// Send a command that changes the SDRAM mode, and wait for the
+    /// SDRAM bank to report `mode`
+    unsafe fn sdram_change_mode(
+        &self,
+        sdram_bank: SdramTargetBank,
+        command: SdramCommand,
+        mode: SdramMode,
+    ) -> Result<(), SdramError> {
+        self.sdram_send_command(command, sdram_bank);
+        self.sdram_wait_mode(sdram_bank, mode)
+    }
+
+    /// Set the refresh rate counter, common to both SDRAM banks
+    unsafe fn sdram_set_refresh_counter(&self, refresh_counter_top: u16) {
+        self.modify(FmcRegister::Sdrtr, |r| {
+            sdrtr::COUNT.set(r, refresh_counter_top.into())
         });
     }
 
     /// Send command to SDRAM
-    unsafe fn send_command(
-        &mut self,
+    ///
+    /// Unlike the FMC on STM32F4/F7 parts, the STM32H7 FMC has no BUSY
+    /// flag in SDSR (only RE, MODES1 and MODES2 are implemented), so
+    /// there is nothing to poll before writing SDCMR. Commands that
+    /// change the SDRAM mode are instead followed by
+    /// [`sdram_wait_mode`](#method.sdram_wait_mode), which has a
+    /// bounded timeout.
+    unsafe fn sdram_send_command(
+        &self,
         mode: SdramCommand,
         target: SdramTargetBank,
     ) {
@@ -420,17 +2322,559 @@ where
         };
 
         // Write to SDCMR
-        self.mem.fmc.sdcmr.modify(|_, w| {
-            w.mrd()
-                .bits(mode_reg)
-                .nrfs()
-                .bits(number_refresh)
-                .ctb1()
-                .bit(b1)
-                .ctb2()
-                .bit(b2)
-                .mode()
-                .bits(cmd)
+        self.modify(FmcRegister::Sdcmr, |r| {
+            let r = sdcmr::MRD.set(r, mode_reg.into());
+            let r = sdcmr::NRFS.set(r, number_refresh.into());
+            let r = sdcmr::CTB1.set(r, b1.into());
+            let r = sdcmr::CTB2.set(r, b2.into());
+            sdcmr::MODE.set(r, cmd)
         });
     }
 }
+
+impl<R: FmcRegisters + ?Sized> SdramRegisters for R {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::is42s16400j_7::Is42s16400j;
+    use crate::is42s32800g_6::Is42s32800g;
+    use crate::mt48lc4m32b2_6::Mt48lc4m32b2 as Mt48lc4m32b2_6;
+    use crate::mt48lc4m32b2_7::Mt48lc4m32b2 as Mt48lc4m32b2_7;
+    use crate::mt48lc4m32b2_75::Mt48lc4m32b2 as Mt48lc4m32b2_75;
+    use crate::registers::FakeFmc;
+    use FmcRegister::*;
+    use SdramError::*;
+
+    /// Kernel clock for an SD clock of 100 MHz
+    const FMC_KER_CK_HZ: u32 = 200_000_000;
+
+    /// Records the total delay
+    #[derive(Default)]
+    struct Delay(u32);
+
+    impl DelayUs<u8> for Delay {
+        fn delay_us(&mut self, us: u8) {
+            self.0 += u32::from(us);
+        }
+    }
+
+    /// Run the initialisation sequence for `IC` on `external_bank`, and
+    /// return the registers
+    fn init<IC: SdramChip>(external_bank: u8) -> FakeFmc {
+        let regs = FakeFmc::new();
+        let mut delay = Delay::default();
+
+        unsafe {
+            init_sequence(
+                &regs,
+                external_bank,
+                &SdramDescriptor::from_chip::<IC>(),
+                FMC_KER_CK_HZ,
+                &mut delay,
+            )
+            .unwrap();
+        }
+        assert_eq!(delay.0, 100);
+        regs
+    }
+
+    fn assert_writes(regs: &FakeFmc, expected: &[(FmcRegister, u32)]) {
+        let writes: Vec<_> = regs.writes().collect();
+        assert_eq!(writes, expected);
+    }
+
+    #[test]
+    fn init_sequence_is42s32800g() {
+        assert_writes(
+            &init::<Is42s32800g>(1),
+            &[
+                (Sdcr1, 0x0000_1ad0),
+                (Sdtr1, 0x0f1f_6fff),
+                (Sdcr1, 0x0000_19e5),
+                (Sdtr1, 0x0112_6461),
+                (Bcr1, 0x8000_30db),
+                (Sdcmr, 0x0000_0031), // Clock enable
+                (Sdcmr, 0x0000_0032), // PALL
+                (Sdcmr, 0x0000_0113), // 8 auto refresh
+                (Sdcmr, 0x0004_6034), // Load mode register
+                (Sdrtr, 0x0000_0c0c),
+            ],
+        );
+    }
+
+    #[test]
+    fn init_sequence_is42s16400j() {
+        assert_writes(
+            &init::<Is42s16400j>(2),
+            &[
+                (Sdcr1, 0x0000_1ad0),
+                (Sdtr1, 0x0f1f_6fff),
+                (Sdcr2, 0x0000_0154),
+                (Sdtr2, 0x01f2_f461),
+                (Bcr1, 0x8000_30db),
+                (Sdcmr, 0x0000_0029), // Clock enable
+                (Sdcmr, 0x0000_002a), // PALL
+                (Sdcmr, 0x0000_010b), // 8 auto refresh
+                (Sdcmr, 0x0004_402c), // Load mode register
+                (Sdrtr, 0x0000_0c0c),
+            ],
+        );
+    }
+
+    #[test]
+    fn init_sequence_mt48lc4m32b2() {
+        let sequence = |sdtr1_common, sdtr1| {
+            [
+                (Sdcr1, 0x0000_1ad0),
+                (Sdtr1, sdtr1_common),
+                (Sdcr1, 0x0000_19e4),
+                (Sdtr1, sdtr1),
+                (Bcr1, 0x8000_30db),
+                (Sdcmr, 0x0000_0031), // Clock enable
+                (Sdcmr, 0x0000_0032), // PALL
+                (Sdcmr, 0x0000_0113), // 8 auto refresh
+                (Sdcmr, 0x0004_6034), // Load mode register
+                (Sdrtr, 0x0000_0c0c),
+            ]
+        };
+
+        assert_writes(
+            &init::<Mt48lc4m32b2_6>(1),
+            &sequence(0x0f1f_5fff, 0x0112_5461),
+        );
+        assert_writes(
+            &init::<Mt48lc4m32b2_7>(1),
+            &sequence(0x0f1f_6fff, 0x0112_6461),
+        );
+        assert_writes(
+            &init::<Mt48lc4m32b2_75>(1),
+            &sequence(0x0f1f_6fff, 0x0112_6471),
+        );
+    }
+
+    #[test]
+    fn init_sequence_invalid() {
+        let regs = FakeFmc::new();
+        let chip = SdramDescriptor::from_chip::<Is42s32800g>();
+        let mut delay = Delay::default();
+
+        let result = unsafe {
+            init_sequence(&regs, 3, &chip, FMC_KER_CK_HZ, &mut delay)
+        };
+        assert_eq!(result, Err(InvalidExternalBank(3)));
+
+        // Too fast for the SDRAM with either divider
+        let result =
+            unsafe { init_sequence(&regs, 1, &chip, 400_000_000, &mut delay) };
+        assert_eq!(
+            result,
+            Err(SdClockTooFast {
+                actual: 133_333_333,
+                max: 100_000_000
+            })
+        );
+        assert_eq!(regs.write_count(), 0);
+        assert_eq!(delay.0, 0);
+    }
+
+    #[test]
+    fn init_sequence_dual_two_chips() {
+        let regs = FakeFmc::new();
+        let mut delay = Delay::default();
+
+        unsafe {
+            init_sequence_dual(
+                &regs,
+                &SdramDescriptor::from_chip::<Is42s32800g>(),
+                &SdramDescriptor::from_chip::<Is42s16400j>(),
+                FMC_KER_CK_HZ,
+                &mut delay,
+            )
+            .unwrap();
+        }
+        assert_eq!(delay.0, 100);
+        assert_writes(
+            &regs,
+            &[
+                (Sdcr1, 0x0000_1ad0),
+                (Sdtr1, 0x0f1f_6fff),
+                (Sdcr1, 0x0000_19e5),
+                (Sdtr1, 0x0112_6461),
+                (Sdcr2, 0x0000_0154),
+                (Sdtr2, 0x01f2_f461),
+                (Bcr1, 0x8000_30db),
+                (Sdcmr, 0x0000_0039), // Clock enable
+                (Sdcmr, 0x0000_003a), // PALL
+                (Sdcmr, 0x0000_011b), // 8 auto refresh
+                (Sdcmr, 0x0004_6034), // Load mode register, bank 1
+                (Sdcmr, 0x0004_402c), // Load mode register, bank 2
+                (Sdrtr, 0x0000_0c0c),
+            ],
+        );
+    }
+
+    #[test]
+    fn init_sequence_dual_same_chip() {
+        let regs = FakeFmc::new();
+        let chip = SdramDescriptor::from_chip::<Is42s32800g>();
+        let mut delay = Delay::default();
+
+        unsafe {
+            init_sequence_dual(&regs, &chip, &chip, FMC_KER_CK_HZ, &mut delay)
+                .unwrap();
+        }
+        // Mode register loaded on both banks at once
+        let load_mode: Vec<_> = regs
+            .writes()
+            .filter(|&(r, v)| r == Sdcmr && sdcmr::MODE.get(v) == 0b100)
+            .collect();
+        assert_eq!(load_mode, [(Sdcmr, 0x0004_603c)]);
+    }
+
+    #[test]
+    fn mode_changes() {
+        use SdramCommand::*;
+        use SdramTargetBank::*;
+
+        let regs = init::<Is42s32800g>(1);
+        regs.clear_writes();
+
+        unsafe {
+            regs.sdram_change_mode(Bank1, Selfrefresh, SdramMode::SelfRefresh)
+                .unwrap();
+            regs.sdram_change_mode(Bank1, NormalMode, SdramMode::Normal)
+                .unwrap();
+            regs.sdram_change_mode(Bank1, Powerdown, SdramMode::PowerDown)
+                .unwrap();
+            regs.sdram_change_mode(Bank1, NormalMode, SdramMode::Normal)
+                .unwrap();
+        }
+        assert_writes(
+            &regs,
+            &[
+                (Sdcmr, 0x0000_0035),
+                (Sdcmr, 0x0000_0030),
+                (Sdcmr, 0x0000_0036),
+                (Sdcmr, 0x0000_0030),
+            ],
+        );
+
+        // Bank 2 is not in self-refresh mode
+        let result = unsafe {
+            regs.sdram_change_mode(Bank1, Selfrefresh, SdramMode::SelfRefresh)
+                .and_then(|()| {
+                    regs.sdram_wait_mode(Bank2, SdramMode::SelfRefresh)
+                })
+        };
+        assert_eq!(result, Err(ModeChangeTimeout));
+    }
+
+    #[test]
+    fn reclock() {
+        let regs = init::<Is42s32800g>(1);
+        let chip = SdramDescriptor::from_chip::<Is42s32800g>();
+        regs.clear_writes();
+
+        // Divider 2 is kept, so the SD clock is 50 MHz
+        unsafe {
+            reclock_sequence(&regs, SdramTargetBank::Bank1, &chip, 100_000_000)
+                .unwrap();
+        }
+        assert_writes(
+            &regs,
+            &[
+                (Sdtr1, 0x0102_3461),
+                (Sdcr1, 0x0000_19e5),
+                (Sdtr1, 0x0001_3231),
+                (Sdrtr, 0x0000_05f2),
+            ],
+        );
+
+        // Too fast for the SDRAM
+        regs.clear_writes();
+        let result = unsafe {
+            reclock_sequence(&regs, SdramTargetBank::Bank1, &chip, 300_000_000)
+        };
+        assert_eq!(
+            result,
+            Err(SdClockTooFast {
+                actual: 150_000_000,
+                max: 100_000_000
+            })
+        );
+        assert_eq!(regs.write_count(), 0);
+    }
+
+    #[test]
+    fn refresh_error() {
+        let regs = init::<Is42s32800g>(1);
+        regs.clear_writes();
+
+        assert!(!regs.sdram_is_refresh_error());
+        assert!(!regs.sdram_clear_refresh_error());
+        assert_eq!(regs.write_count(), 0);
+
+        regs.set_refresh_error();
+        let count = REFRESH_ERRORS.load(Ordering::Relaxed);
+        assert!(regs.sdram_is_refresh_error());
+        assert!(regs.sdram_clear_refresh_error());
+        assert!(!regs.sdram_is_refresh_error());
+        assert!(REFRESH_ERRORS.load(Ordering::Relaxed) > count);
+        // CRE written, refresh counter unchanged
+        assert_writes(&regs, &[(Sdrtr, 0x0000_0c0d)]);
+        assert_eq!(regs.read(Sdrtr), 0x0000_0c0c);
+
+        regs.sdram_listen_refresh_error(true);
+        assert_eq!(sdrtr::REIE.get(regs.read(Sdrtr)), 1);
+    }
+
+    #[test]
+    fn time_to_cycles() {
+        // Rounded up to whole cycles
+        assert_eq!(SdramTime::ns(20).to_cycles(100_000_000), 2);
+        assert_eq!(SdramTime::ns(21).to_cycles(100_000_000), 3);
+        assert_eq!(SdramTime::ns(18).to_cycles(133_333_333), 3);
+        assert_eq!(SdramTime::ns(1).to_cycles(1_000_000), 1);
+        assert_eq!(SdramTime::ns(0).to_cycles(100_000_000), 0);
+
+        // At least the minimum number of cycles
+        assert_eq!(SdramTime::cycles(2).to_cycles(100_000_000), 2);
+        let time = SdramTime::ns(15).min_cycles(2);
+        assert_eq!(time.to_cycles(100_000_000), 2);
+        assert_eq!(time.to_cycles(200_000_000), 3);
+    }
+
+    #[test]
+    fn write_recovery_at_slow_clocks() {
+        let timing = Is42s32800g::TIMING;
+
+        for &sd_clock_hz in &[4_000_000, 20_000_000, 25_000_000] {
+            let cycles = timing.cycles(sd_clock_hz).unwrap();
+            assert_eq!(cycles.write_recovery, 1);
+        }
+        let cycles = timing.cycles(100_000_000).unwrap();
+        assert_eq!(cycles.write_recovery, 3);
+    }
+
+    fn descriptor() -> SdramDescriptor {
+        SdramDescriptor::from_chip::<Is42s32800g>()
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert_eq!(descriptor().validate(), Ok(()));
+
+        let mut d = descriptor();
+        d.config.cas_latency = 4;
+        assert_eq!(validate(&d.config, &d.timing), Err(InvalidCasLatency(4)));
+
+        let mut d = descriptor();
+        d.config.row_bits = 14;
+        assert_eq!(d.validate(), Err(InvalidRowBits(14)));
+
+        let mut d = descriptor();
+        d.config.column_bits = 7;
+        assert_eq!(d.validate(), Err(InvalidColumnBits(7)));
+
+        let mut d = descriptor();
+        d.config.read_pipe_delay_cycles = 3;
+        assert_eq!(d.validate(), Err(InvalidReadPipeDelay(3)));
+
+        let mut d = descriptor();
+        d.config.internal_banks = 3;
+        assert_eq!(d.validate(), Err(InvalidInternalBanks(3)));
+
+        let mut d = descriptor();
+        d.config.memory_data_width = 64;
+        assert_eq!(d.validate(), Err(InvalidDataWidth(64)));
+    }
+
+    #[test]
+    fn invalid_timing() {
+        let mut d = descriptor();
+        d.timing.startup_delay_ns = 255_001;
+        assert_eq!(d.validate(), Err(StartupDelayOutOfRange(256)));
+
+        // 17 cycles at 100 MHz
+        let mut d = descriptor();
+        d.timing.row_cycle = SdramTime::ns(170);
+        assert_eq!(d.validate(), Err(TimingOutOfRange("row_cycle")));
+
+        let mut d = descriptor();
+        d.timing.mode_register_to_active = SdramTime::cycles(0);
+        assert_eq!(
+            d.validate(),
+            Err(TimingOutOfRange("mode_register_to_active"))
+        );
+    }
+
+    #[test]
+    fn cas_latency_mismatch() {
+        let mut d = descriptor();
+        d.mode_register = d.mode_register.cas_latency(2);
+        assert_eq!(validate(&d.config, &d.timing), Ok(()));
+        assert_eq!(
+            d.validate(),
+            Err(CasLatencyMismatch {
+                mode_register: 2,
+                config: 3
+            })
+        );
+    }
+
+    #[test]
+    fn extended_mode_register_bank_address() {
+        let emr = ExtendedModeRegister::new()
+            .partial_array_self_refresh(PartialArraySelfRefresh::Quarter);
+        assert_eq!(emr.bits(), 0b010);
+
+        // BA1 follows A0 ~ A10
+        assert_eq!(emr.load_value(11), Ok(1 << 12 | 0b010));
+        // BA1 follows A0 ~ A11
+        assert_eq!(emr.load_value(12), Ok(1 << 13 | 0b010));
+        assert_eq!(emr.load_value(13), Err(ExtendedModeRegisterRowBits(13)));
+    }
+
+    #[test]
+    fn partial_array_self_refresh() {
+        let regs = FakeFmc::new();
+        let mut emr = Some(ExtendedModeRegister::new());
+
+        unsafe {
+            load_partial_array_self_refresh(
+                &regs,
+                SdramTargetBank::Bank2,
+                &mut emr,
+                12,
+                PartialArraySelfRefresh::Half,
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            emr,
+            Some(
+                ExtendedModeRegister::new()
+                    .partial_array_self_refresh(PartialArraySelfRefresh::Half)
+            )
+        );
+        // PALL, then LOAD MODE REGISTER with BA1 set, on bank 2
+        let mut writes = regs.writes();
+        assert_eq!(writes.next(), Some((Sdcmr, 0x2a)));
+        assert_eq!(writes.next(), Some((Sdcmr, (1 << 13 | 0b001) << 9 | 0x2c)));
+        assert_eq!(writes.next(), None);
+
+        // No extended mode register
+        let mut emr = None;
+        let result = unsafe {
+            load_partial_array_self_refresh(
+                &regs,
+                SdramTargetBank::Bank1,
+                &mut emr,
+                12,
+                PartialArraySelfRefresh::Half,
+            )
+        };
+        assert_eq!(result, Err(NoExtendedModeRegister));
+        assert_eq!(emr, None);
+    }
+
+    /// Pins with 12 address lines and 2 internal banks, on a 16-bit bus
+    struct Pins12Bit2Bank;
+
+    impl PinsSdram<stm32::FMC> for Pins12Bit2Bank {
+        const EXTERNAL_BANK: u8 = 1;
+        const NUMBER_INTERNAL_BANKS: u8 = 2;
+        const ADDRESS_LINES: u8 = 12;
+        const DATA_WIDTH: u8 = 16;
+    }
+
+    /// Pins with 11 address lines and 4 internal banks, on a 16-bit bus
+    struct Pins11Bit4Bank;
+
+    impl PinsSdram<stm32::FMC> for Pins11Bit4Bank {
+        const EXTERNAL_BANK: u8 = 1;
+        const NUMBER_INTERNAL_BANKS: u8 = 4;
+        const ADDRESS_LINES: u8 = 11;
+        const DATA_WIDTH: u8 = 16;
+    }
+
+    #[test]
+    fn pins_dynamic() {
+        let config = FmcSdramConfiguration {
+            internal_banks: 2,
+            ..Is42s16400j::CONFIG
+        };
+        assert_eq!(check_pins_dynamic::<Pins12Bit2Bank>(&config), Ok(()));
+
+        assert_eq!(
+            check_pins_dynamic::<Pins12Bit2Bank>(&Is42s32800g::CONFIG),
+            Err(PinsDataWidthMismatch {
+                pins: 16,
+                sdram: 32
+            })
+        );
+        assert_eq!(
+            check_pins_dynamic::<Pins12Bit2Bank>(&FmcSdramConfiguration {
+                row_bits: 13,
+                ..config
+            }),
+            Err(NotEnoughAddressPins {
+                pins: 12,
+                sdram: 13
+            })
+        );
+        assert_eq!(
+            check_pins_dynamic::<Pins12Bit2Bank>(&Is42s16400j::CONFIG),
+            Err(NotEnoughBankPins { pins: 2, sdram: 4 })
+        );
+    }
+
+    #[test]
+    fn pins_dynamic_column_bits() {
+        // 11 rows x 10 columns
+        let config = FmcSdramConfiguration {
+            row_bits: 11,
+            column_bits: 10,
+            ..Is42s16400j::CONFIG
+        };
+        assert_eq!(check_pins_dynamic::<Pins11Bit4Bank>(&config), Ok(()));
+
+        // 11 rows x 11 columns. Column bit 10 is on A11
+        let config = FmcSdramConfiguration {
+            column_bits: 11,
+            ..config
+        };
+        assert_eq!(
+            check_pins_dynamic::<Pins11Bit4Bank>(&config),
+            Err(NotEnoughAddressPins {
+                pins: 11,
+                sdram: 12
+            })
+        );
+        let config = FmcSdramConfiguration {
+            internal_banks: 2,
+            ..config
+        };
+        assert_eq!(check_pins_dynamic::<Pins12Bit2Bank>(&config), Ok(()));
+    }
+
+    #[test]
+    fn common_write_recovery() {
+        let timing1 = Is42s32800g::TIMING;
+        let timing2 = FmcSdramTiming {
+            active_to_precharge: SdramTime::ns(70),
+            ..timing1
+        };
+        let cycles1 = timing1.cycles(100_000_000).unwrap();
+        let cycles2 = timing2.cycles(100_000_000).unwrap();
+        assert_eq!(cycles1.write_recovery, 3);
+        assert_eq!(cycles2.write_recovery, 5);
+
+        let (common1, common2) = SdramCycles::common(cycles1, cycles2).unwrap();
+        assert_eq!(common1.write_recovery, 5);
+        assert_eq!(common2.write_recovery, 5);
+        assert_eq!(common1.row_cycle, common2.row_cycle);
+        assert_eq!(common1.row_precharge, common2.row_precharge);
+    }
+}